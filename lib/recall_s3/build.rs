@@ -0,0 +1,35 @@
+use std::path::Path;
+use std::process::Command;
+
+const GIT_DIR: &str = "../../.git";
+
+fn main() {
+    let git_sha = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|sha| sha.trim().to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+
+    println!("cargo:rustc-env=RECALL_S3_GIT_SHA={git_sha}");
+
+    // HEAD only changes on checkout; commits move the branch it points to, which lives either in
+    // its own ref file or in packed-refs. Missing files would rerun the script on every build.
+    let git_dir = Path::new(GIT_DIR);
+    let head = git_dir.join("HEAD");
+    println!("cargo:rerun-if-changed={}", head.display());
+    let head_ref = std::fs::read_to_string(&head)
+        .ok()
+        .and_then(|head| head.strip_prefix("ref: ").map(|r| r.trim().to_string()));
+    for path in head_ref
+        .map(|r| git_dir.join(r))
+        .into_iter()
+        .chain([git_dir.join("packed-refs")])
+    {
+        if path.exists() {
+            println!("cargo:rerun-if-changed={}", path.display());
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use async_trait::async_trait;
+use recall_provider::fvm_shared::address::Address;
+use recall_provider::json_rpc::JsonRpcProvider;
+use recall_provider::query::FvmQueryHeight;
+use recall_provider::Client;
+use recall_sdk::credits::Credits;
+use serde::Serialize;
+
+/// Storage credit of an account. Writes through the gateway spend the wallet's credit, so they
+/// fail once `credit_free` runs out. Amounts are decimal strings, as they don't fit in JSON
+/// numbers.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CreditBalance {
+    pub address: String,
+    /// Credit left for new writes.
+    pub credit_free: String,
+    /// Credit committed to blobs already stored.
+    pub credit_committed: String,
+}
+
+/// Where balances are read from: the chain, through the provider.
+#[async_trait]
+pub trait BalanceSource: Send + Sync {
+    async fn credit_balance(&self, address: Address) -> anyhow::Result<CreditBalance>;
+}
+
+#[async_trait]
+impl<C: Client + Send + Sync> BalanceSource for JsonRpcProvider<C> {
+    async fn credit_balance(&self, address: Address) -> anyhow::Result<CreditBalance> {
+        let balance = Credits::balance(self, address, FvmQueryHeight::Committed).await?;
+        Ok(CreditBalance {
+            address: address.to_string(),
+            credit_free: balance.credit_free.to_string(),
+            credit_committed: balance.credit_committed.to_string(),
+        })
+    }
+}
+
+/// The account's credit balance as pretty-printed JSON.
+pub async fn balance_report(
+    source: &dyn BalanceSource,
+    address: Address,
+) -> anyhow::Result<String> {
+    let balance = source.credit_balance(address).await?;
+    Ok(serde_json::to_string_pretty(&balance)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::balance::{balance_report, BalanceSource, CreditBalance};
+    use async_trait::async_trait;
+    use recall_provider::fvm_shared::address::Address;
+
+    /// Reports the same credit for every account.
+    struct FixedBalance {
+        credit_free: u64,
+        credit_committed: u64,
+    }
+
+    #[async_trait]
+    impl BalanceSource for FixedBalance {
+        async fn credit_balance(&self, address: Address) -> anyhow::Result<CreditBalance> {
+            Ok(CreditBalance {
+                address: address.to_string(),
+                credit_free: self.credit_free.to_string(),
+                credit_committed: self.credit_committed.to_string(),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_balance_report() {
+        let source = FixedBalance {
+            credit_free: 5000,
+            credit_committed: 1200,
+        };
+        let address = Address::new_id(1001);
+
+        let report = balance_report(&source, address).await.unwrap();
+        let json: serde_json::Value = serde_json::from_str(&report).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "address": address.to_string(),
+                "credit_free": "5000",
+                "credit_committed": "1200",
+            })
+        );
+    }
+}
@@ -4,7 +4,7 @@ use s3s::dto::BucketName;
 use s3s::{s3_error, S3Error, S3ErrorCode};
 use std::str::FromStr;
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 pub struct BucketNameWithOwner {
     name: String,
     owner: Address,
@@ -35,6 +35,48 @@ impl BucketNameWithOwner {
     }
 }
 
+/// How bucket names address a bucket, reported when a name doesn't.
+pub static BUCKET_PATH_FORMAT: &str = "bucket must be formatted as <owner-address>.<name>";
+
+/// Error for a bucket name that doesn't say both whose bucket it is and which one. It's the
+/// client's to fix, so it's a 400 rather than something worth retrying.
+pub fn invalid_bucket_path() -> S3Error {
+    s3_error!(InvalidBucketName, "{}", BUCKET_PATH_FORMAT)
+}
+
+/// Whether `name` is an owner address with no bucket name after it, e.g. `0x…` or `0x….`.
+pub fn is_owner_only(name: &str) -> bool {
+    let owner = name.strip_suffix('.').unwrap_or(name);
+    !owner.contains('.') && ethers::types::Address::from_str(owner).is_ok()
+}
+
+/// Length of a hex address with its `0x` prefix.
+const ADDRESS_LEN: usize = 42;
+
+/// Checks what a bucket name says about its owner before it's resolved. A name that's only an
+/// owner address doesn't say which bucket. A first label shaped like an address, i.e. `0x` and 40
+/// more characters, that isn't one is a mistyped owner rather than part of a bucket name, so it's
+/// refused instead of being looked up as the wallet's bucket and reported missing. Shorter labels
+/// such as the `0xab` of `0xab.logs` are left to the bucket name.
+pub fn check_owner_prefix(name: &str) -> Result<(), S3Error> {
+    if is_owner_only(name) {
+        return Err(invalid_bucket_path());
+    }
+    match name.split_once('.') {
+        Some((owner, _))
+            if owner.len() == ADDRESS_LEN
+                && owner.starts_with("0x")
+                && ethers::types::Address::from_str(owner).is_err() =>
+        {
+            Err(s3_error!(
+                InvalidBucketName,
+                "owner {owner} isn't an address, {BUCKET_PATH_FORMAT}"
+            ))
+        }
+        _ => Ok(()),
+    }
+}
+
 pub fn split_eth_address(name: &str) -> Option<(String, String)> {
     let parts = name.split(".").collect::<Vec<_>>();
     if parts.len() == 1 {
@@ -86,7 +128,49 @@ pub fn check_bucket_name(name: &str) -> bool {
 
 #[cfg(test)]
 mod tests {
-    use crate::bucket::BucketNameWithOwner;
+    use crate::bucket::{
+        check_owner_prefix, invalid_bucket_path, is_owner_only, BucketNameWithOwner,
+    };
+    use s3s::S3ErrorCode;
+
+    #[test]
+    fn test_owner_only() {
+        assert!(is_owner_only("0xe1209fb9aa2d08c8541297ec06ee6bbb63b10edc"));
+        assert!(is_owner_only("0xe1209fb9aa2d08c8541297ec06ee6bbb63b10edc."));
+        assert!(!is_owner_only(
+            "0xe1209fb9aa2d08c8541297ec06ee6bbb63b10edc.foo"
+        ));
+        assert!(!is_owner_only("foo"));
+
+        let err = invalid_bucket_path();
+        assert_eq!(*err.code(), S3ErrorCode::InvalidBucketName);
+        assert_eq!(
+            err.message(),
+            Some("bucket must be formatted as <owner-address>.<name>")
+        );
+    }
+
+    #[test]
+    fn test_check_owner_prefix() {
+        for name in [
+            "foo",
+            "foo.bar",
+            "0xab.logs",
+            "0xnotanaddress.foo",
+            "0xe1209fb9aa2d08c8541297ec06ee6bbb63b10edc.foo",
+        ] {
+            assert!(check_owner_prefix(name).is_ok(), "{name}");
+        }
+        for name in [
+            "0xe1209fb9aa2d08c8541297ec06ee6bbb63b10edc",
+            // not hex
+            "0xe1209fb9aa2d08c8541297ec06ee6bbb63b10edz.foo",
+        ] {
+            let err = check_owner_prefix(name).unwrap_err();
+            assert_eq!(*err.code(), S3ErrorCode::InvalidBucketName, "{name}");
+        }
+    }
+
     #[test]
     fn test_bucket_name_with_owner() {
         let bucket = BucketNameWithOwner::from(
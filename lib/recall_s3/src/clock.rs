@@ -0,0 +1,62 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+/// Source of the timestamps recorded on buckets and objects.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> SystemTime;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// A clock that only moves when told to. Clones share the same time.
+#[derive(Clone)]
+pub struct MockClock {
+    now: Arc<Mutex<SystemTime>>,
+}
+
+impl MockClock {
+    pub fn new(now: SystemTime) -> Self {
+        Self {
+            now: Arc::new(Mutex::new(now)),
+        }
+    }
+
+    pub fn set(&self, now: SystemTime) {
+        *self.now.lock().expect("mock clock lock poisoned") = now;
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *self.now.lock().expect("mock clock lock poisoned") += by;
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> SystemTime {
+        *self.now.lock().expect("mock clock lock poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::clock::{Clock, MockClock};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn test_mock_clock() {
+        let clock = MockClock::new(UNIX_EPOCH + Duration::from_secs(100));
+        let handle = clock.clone();
+        assert_eq!(clock.now(), UNIX_EPOCH + Duration::from_secs(100));
+
+        handle.advance(Duration::from_secs(5));
+        assert_eq!(clock.now(), UNIX_EPOCH + Duration::from_secs(105));
+
+        handle.set(UNIX_EPOCH);
+        assert_eq!(clock.now(), UNIX_EPOCH);
+    }
+}
@@ -0,0 +1,287 @@
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use bytes::Bytes;
+use flate2::write::{GzDecoder, GzEncoder};
+use flate2::Compression;
+use futures::StreamExt;
+use http::header::{ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_TYPE};
+use http::HeaderMap;
+use tokio_util::io::{ReaderStream, StreamReader};
+
+use crate::transform::BoxReader;
+
+/// zstd's own default, a good trade of speed for ratio.
+const ZSTD_LEVEL: i32 = 3;
+
+/// Whether the request's `Accept-Encoding` allows a gzip-encoded response.
+pub fn accepts_gzip(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(ACCEPT_ENCODING)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|coding| {
+            let mut params = coding.split(';');
+            let name = params.next().unwrap_or_default().trim();
+            let disabled = params.any(|p| {
+                p.trim()
+                    .strip_prefix("q=")
+                    .and_then(|q| q.parse::<f32>().ok())
+                    .is_some_and(|q| q <= 0.0)
+            });
+            name.eq_ignore_ascii_case("gzip") && !disabled
+        })
+}
+
+/// Whether a response is an XML document that hasn't been encoded yet. Object bodies never
+/// match, so their stored content-encoding is left untouched.
+pub fn is_compressible_xml(headers: &HeaderMap) -> bool {
+    if headers.contains_key(CONTENT_ENCODING) {
+        return false;
+    }
+
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<mime::Mime>().ok())
+        .is_some_and(|m| m.subtype() == mime::XML)
+}
+
+pub fn gzip(input: impl AsRef<[u8]>) -> std::io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(input.as_ref())?;
+    encoder.finish()
+}
+
+/// How an object's bytes are compressed in storage, chosen by its writer. It's recorded with the
+/// object and undone on every read, so clients only ever see what they stored; the object's
+/// `Content-Encoding` is unrelated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageCompression {
+    Gzip,
+    Zstd,
+}
+
+impl StorageCompression {
+    pub fn compressor(self) -> io::Result<Compressor> {
+        Ok(match self {
+            Self::Gzip => Compressor::Gzip(GzEncoder::new(Vec::new(), Compression::default())),
+            Self::Zstd => {
+                Compressor::Zstd(zstd::stream::write::Encoder::new(Vec::new(), ZSTD_LEVEL)?)
+            }
+        })
+    }
+
+    pub fn decompressor(self) -> io::Result<Decompressor> {
+        Ok(match self {
+            Self::Gzip => Decompressor::Gzip(GzDecoder::new(Vec::new())),
+            Self::Zstd => Decompressor::Zstd(zstd::stream::write::Decoder::new(Vec::new())?),
+        })
+    }
+}
+
+impl FromStr for StorageCompression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "gzip" => Ok(Self::Gzip),
+            "zstd" => Ok(Self::Zstd),
+            _ => Err(format!("unknown compression {s}, expected gzip or zstd")),
+        }
+    }
+}
+
+impl fmt::Display for StorageCompression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Gzip => f.write_str("gzip"),
+            Self::Zstd => f.write_str("zstd"),
+        }
+    }
+}
+
+/// Compresses a body chunk by chunk as it streams through, so it's never held whole.
+pub enum Compressor {
+    Gzip(GzEncoder<Vec<u8>>),
+    Zstd(zstd::stream::write::Encoder<'static, Vec<u8>>),
+}
+
+impl Compressor {
+    /// Compresses `chunk`, returning whatever compressed output is ready so far.
+    pub fn push(&mut self, chunk: &[u8]) -> io::Result<Vec<u8>> {
+        let out = match self {
+            Self::Gzip(e) => {
+                e.write_all(chunk)?;
+                e.get_mut()
+            }
+            Self::Zstd(e) => {
+                e.write_all(chunk)?;
+                e.get_mut()
+            }
+        };
+        Ok(std::mem::take(out))
+    }
+
+    /// The rest of the compressed output, once the whole body has been pushed.
+    pub fn finish(self) -> io::Result<Vec<u8>> {
+        match self {
+            Self::Gzip(e) => e.finish(),
+            Self::Zstd(e) => e.finish(),
+        }
+    }
+}
+
+/// Counterpart of [`Compressor`]: decompresses input as it's pushed, without blocking.
+pub enum Decompressor {
+    Gzip(GzDecoder<Vec<u8>>),
+    Zstd(zstd::stream::write::Decoder<'static, Vec<u8>>),
+}
+
+impl Decompressor {
+    pub fn push(&mut self, chunk: &[u8]) -> io::Result<Vec<u8>> {
+        let out = match self {
+            Self::Gzip(d) => {
+                d.write_all(chunk)?;
+                d.get_mut()
+            }
+            Self::Zstd(d) => {
+                d.write_all(chunk)?;
+                d.get_mut()
+            }
+        };
+        Ok(std::mem::take(out))
+    }
+
+    pub fn finish(self) -> io::Result<Vec<u8>> {
+        match self {
+            Self::Gzip(d) => d.finish(),
+            Self::Zstd(mut d) => {
+                d.flush()?;
+                Ok(d.into_inner())
+            }
+        }
+    }
+}
+
+/// Decompresses the stored bytes of an object as they're read.
+pub fn decompress_reader(
+    compression: StorageCompression,
+    reader: BoxReader,
+) -> io::Result<BoxReader> {
+    let decompressor = compression.decompressor()?;
+    let chunks = futures::stream::unfold(
+        (ReaderStream::new(reader), Some(decompressor)),
+        |(mut input, decompressor)| async move {
+            let mut decompressor = decompressor?;
+            loop {
+                let out = match input.next().await {
+                    Some(Ok(chunk)) => decompressor.push(&chunk),
+                    Some(Err(e)) => Err(e),
+                    None => {
+                        let out = decompressor.finish();
+                        return Some((out.map(Bytes::from), (input, None)));
+                    }
+                };
+                match out {
+                    Ok(out) if out.is_empty() => continue,
+                    Ok(out) => return Some((Ok(Bytes::from(out)), (input, Some(decompressor)))),
+                    Err(e) => return Some((Err(e), (input, None))),
+                }
+            }
+        },
+    );
+    Ok(Box::pin(StreamReader::new(chunks)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compress::{
+        accepts_gzip, decompress_reader, gzip, is_compressible_xml, StorageCompression,
+    };
+    use flate2::read::GzDecoder;
+    use http::header::{ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_TYPE};
+    use http::{HeaderMap, HeaderValue};
+    use std::io::{Cursor, Read};
+    use tokio::io::AsyncReadExt;
+
+    #[test]
+    fn test_accepts_gzip() {
+        let mut headers = HeaderMap::new();
+        assert!(!accepts_gzip(&headers));
+
+        headers.insert(ACCEPT_ENCODING, HeaderValue::from_static("br, gzip;q=0.8"));
+        assert!(accepts_gzip(&headers));
+
+        headers.insert(
+            ACCEPT_ENCODING,
+            HeaderValue::from_static("gzip;q=0, identity"),
+        );
+        assert!(!accepts_gzip(&headers));
+    }
+
+    #[test]
+    fn test_is_compressible_xml() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/xml"));
+        assert!(is_compressible_xml(&headers));
+
+        headers.insert(CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+        assert!(!is_compressible_xml(&headers));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("application/octet-stream"),
+        );
+        assert!(!is_compressible_xml(&headers));
+    }
+
+    #[test]
+    fn test_gzip_listing() {
+        let mut xml = String::from(
+            r#"<?xml version="1.0" encoding="UTF-8"?><ListBucketResult><Name>foo</Name>"#,
+        );
+        for i in 0..1000 {
+            xml.push_str(&format!(
+                "<Contents><Key>this/is/a/key-{i}</Key></Contents>"
+            ));
+        }
+        xml.push_str("</ListBucketResult>");
+
+        let compressed = gzip(&xml).unwrap();
+        assert!(compressed.len() < xml.len());
+
+        let mut decompressed = String::new();
+        GzDecoder::new(compressed.as_slice())
+            .read_to_string(&mut decompressed)
+            .unwrap();
+        assert_eq!(xml, decompressed);
+    }
+
+    #[tokio::test]
+    async fn test_storage_compression_round_trip() {
+        let body = "all work and no play makes jack a dull boy\n".repeat(10_000);
+
+        for compression in [StorageCompression::Gzip, StorageCompression::Zstd] {
+            let mut compressor = compression.compressor().unwrap();
+            let mut stored = Vec::new();
+            // fed in uneven chunks, like a streamed body
+            for chunk in body.as_bytes().chunks(1000) {
+                stored.extend(compressor.push(chunk).unwrap());
+            }
+            stored.extend(compressor.finish().unwrap());
+            assert!(stored.len() < body.len() / 10, "{compression}");
+
+            let mut reader = decompress_reader(compression, Box::pin(Cursor::new(stored))).unwrap();
+            let mut read = String::new();
+            reader.read_to_string(&mut read).await.unwrap();
+            assert_eq!(read, body, "{compression}");
+        }
+
+        assert_eq!("ZSTD".parse(), Ok(StorageCompression::Zstd));
+        assert!("br".parse::<StorageCompression>().is_err());
+    }
+}
@@ -0,0 +1,69 @@
+use std::borrow::Cow;
+
+/// Header carrying the id generated for each request, as AWS does.
+pub static REQUEST_ID_HEADER: &str = "x-amz-request-id";
+
+/// Adds the `Resource` and `RequestId` fields AWS clients expect to a serialized S3 error, unless
+/// already present. Returns `None` if `xml` isn't an error document.
+pub fn annotate_error_xml(xml: &[u8], resource: &str, request_id: &str) -> Option<Vec<u8>> {
+    let xml = std::str::from_utf8(xml).ok()?;
+    let end = xml.rfind("</Error>")?;
+
+    let mut out = String::with_capacity(xml.len() + resource.len() + request_id.len() + 48);
+    out.push_str(&xml[..end]);
+    if !xml.contains("<Resource>") {
+        out.push_str("<Resource>");
+        out.push_str(&escape(resource));
+        out.push_str("</Resource>");
+    }
+    if !xml.contains("<RequestId>") {
+        out.push_str("<RequestId>");
+        out.push_str(&escape(request_id));
+        out.push_str("</RequestId>");
+    }
+    out.push_str(&xml[end..]);
+    Some(out.into_bytes())
+}
+
+fn escape(s: &str) -> Cow<'_, str> {
+    if !s.contains(['&', '<', '>', '"', '\'']) {
+        return Cow::Borrowed(s);
+    }
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            c => out.push(c),
+        }
+    }
+    Cow::Owned(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error_context::annotate_error_xml;
+
+    #[test]
+    fn test_annotate_error_xml() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?><Error><Code>NoSuchKey</Code></Error>"#;
+        let annotated = annotate_error_xml(xml.as_bytes(), "/foo/a&b.txt", "abc123").unwrap();
+        assert_eq!(
+            String::from_utf8(annotated).unwrap(),
+            r#"<?xml version="1.0" encoding="UTF-8"?><Error><Code>NoSuchKey</Code><Resource>/foo/a&amp;b.txt</Resource><RequestId>abc123</RequestId></Error>"#
+        );
+
+        // Existing fields are kept.
+        let xml = "<Error><Code>NoSuchKey</Code><RequestId>xyz</RequestId></Error>";
+        let annotated = annotate_error_xml(xml.as_bytes(), "/foo", "abc123").unwrap();
+        assert_eq!(
+            String::from_utf8(annotated).unwrap(),
+            "<Error><Code>NoSuchKey</Code><RequestId>xyz</RequestId><Resource>/foo</Resource></Error>"
+        );
+
+        assert!(annotate_error_xml(b"<ListBucketResult/>", "/foo", "abc123").is_none());
+    }
+}
@@ -0,0 +1,254 @@
+use std::fmt;
+use std::str::FromStr;
+
+use md5::{Digest, Md5};
+use sha2::Sha256;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use uuid::Uuid;
+
+use crate::utils::{hex, HashReader};
+
+/// Bytes of the random digest in etags made with [`ETagAlgorithm::None`]. It's shorter than
+/// any real digest, so these etags are told apart from computed ones.
+const SYNTHETIC_DIGEST_LEN: usize = 8;
+
+/// How object etags are computed. Changing it gives new uploads differently shaped etags, which
+/// breaks etag-based sync against objects stored before the change.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ETagAlgorithm {
+    /// `"<md5>"`, or `"<md5 of part md5s>-<n>"` for multipart uploads, as S3 does.
+    #[default]
+    Md5,
+    /// The same shapes with SHA-256 in place of MD5.
+    Sha256,
+    /// No hashing: every object and part gets a random `"<16 hex digits>"` etag, saving the CPU
+    /// of hashing each byte. The etag says nothing about the content, so etag-based sync sees
+    /// every object as changed and stored objects can't be verified.
+    None,
+}
+
+impl ETagAlgorithm {
+    pub fn hasher(self) -> ETagHasher {
+        match self {
+            Self::Md5 => ETagHasher::Md5(Md5::new()),
+            Self::Sha256 => ETagHasher::Sha256(Sha256::new()),
+            Self::None => ETagHasher::None,
+        }
+    }
+}
+
+impl FromStr for ETagAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "md5" => Ok(Self::Md5),
+            "sha256" => Ok(Self::Sha256),
+            "none" => Ok(Self::None),
+            _ => Err(format!(
+                "unknown etag algorithm {s}, expected md5, sha256 or none"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ETagAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Md5 => f.write_str("md5"),
+            Self::Sha256 => f.write_str("sha256"),
+            Self::None => f.write_str("none"),
+        }
+    }
+}
+
+pub enum ETagHasher {
+    Md5(Md5),
+    Sha256(Sha256),
+    /// Ignores the data and makes up a digest.
+    None,
+}
+
+impl ETagHasher {
+    pub fn update(&mut self, data: impl AsRef<[u8]>) {
+        match self {
+            Self::Md5(h) => h.update(data),
+            Self::Sha256(h) => h.update(data),
+            Self::None => {}
+        }
+    }
+
+    pub fn finalize(self) -> Vec<u8> {
+        match self {
+            Self::Md5(h) => h.finalize().to_vec(),
+            Self::Sha256(h) => h.finalize().to_vec(),
+            Self::None => Uuid::new_v4().as_bytes()[..SYNTHETIC_DIGEST_LEN].to_vec(),
+        }
+    }
+
+    /// The quoted etag of a single object or part.
+    pub fn e_tag(self) -> String {
+        format!("\"{}\"", hex(self.finalize()))
+    }
+}
+
+/// Outcome of checking an object's stored bytes against its recorded etag.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ETagCheck {
+    Match,
+    Mismatch {
+        expected: String,
+        actual: String,
+    },
+    /// The etag can't be recomputed, e.g. a multipart etag without a recorded part size.
+    Unverifiable(&'static str),
+}
+
+/// Part size a multipart etag can be recomputed with: the size of every part but the last, if
+/// they're all the same. Clients almost always upload this way.
+pub fn uniform_part_size(part_sizes: &[u64]) -> Option<u64> {
+    let (first, rest) = part_sizes.split_first()?;
+    let (_, middle) = rest.split_last().unwrap_or((first, &[]));
+    middle.iter().all(|size| size == first).then_some(*first)
+}
+
+/// Recomputes the etag of `reader`'s bytes in the shape of `e_tag` and compares the two. The
+/// algorithm is told apart by digest length, so etags from before a change of algorithm still
+/// check out. Multipart etags need the size their parts were uploaded in.
+pub async fn verify_e_tag<R: AsyncRead + Unpin>(
+    mut reader: R,
+    e_tag: &str,
+    part_size: Option<u64>,
+) -> std::io::Result<ETagCheck> {
+    let unquoted = e_tag.trim_matches('"');
+    let (digest, multipart) = match unquoted.split_once('-') {
+        Some((digest, _)) => (digest, true),
+        None => (unquoted, false),
+    };
+    let algorithm = match digest.len() {
+        32 => ETagAlgorithm::Md5,
+        64 => ETagAlgorithm::Sha256,
+        len if len == SYNTHETIC_DIGEST_LEN * 2 => {
+            return Ok(ETagCheck::Unverifiable(
+                "etag wasn't computed from the content",
+            ))
+        }
+        _ => return Ok(ETagCheck::Unverifiable("unknown etag format")),
+    };
+
+    let actual = if multipart {
+        let Some(part_size) = part_size.filter(|size| *size > 0) else {
+            return Ok(ETagCheck::Unverifiable("part size not recorded"));
+        };
+        let mut composite = algorithm.hasher();
+        let mut parts = 0;
+        loop {
+            let mut part = HashReader::new((&mut reader).take(part_size), algorithm.hasher());
+            let read = tokio::io::copy(&mut part, &mut tokio::io::sink()).await?;
+            if read == 0 && parts > 0 {
+                break;
+            }
+            composite.update(part.finalize());
+            parts += 1;
+            if read < part_size {
+                break;
+            }
+        }
+        format!("\"{}-{parts}\"", hex(composite.finalize()))
+    } else {
+        let mut whole = HashReader::new(reader, algorithm.hasher());
+        tokio::io::copy(&mut whole, &mut tokio::io::sink()).await?;
+        format!("\"{}\"", hex(whole.finalize()))
+    };
+
+    Ok(if actual == e_tag {
+        ETagCheck::Match
+    } else {
+        ETagCheck::Mismatch {
+            expected: e_tag.to_string(),
+            actual,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::etag::{uniform_part_size, verify_e_tag, ETagAlgorithm, ETagCheck};
+
+    #[test]
+    fn test_e_tag() {
+        let mut md5 = ETagAlgorithm::Md5.hasher();
+        md5.update("The quick brown fox jumps over the lazy dog");
+        assert_eq!(md5.e_tag(), "\"9e107d9d372bb6826bd81d3542a419d6\"");
+
+        let mut sha256 = ETagAlgorithm::Sha256.hasher();
+        sha256.update("The quick brown fox jumps over the lazy dog");
+        assert_eq!(
+            sha256.e_tag(),
+            "\"d7a8fbb307d7809469ca9abcb0082e4f8d5651e46d3cdb762d02d0bf37c9e592\""
+        );
+
+        // Nothing is hashed, so the same content gets a different etag every time.
+        let none = |data: &str| {
+            let mut hasher = ETagAlgorithm::None.hasher();
+            hasher.update(data);
+            hasher.e_tag()
+        };
+        let e_tag = none("The quick brown fox jumps over the lazy dog");
+        assert_eq!(e_tag.len(), 18);
+        assert_ne!(e_tag, none("The quick brown fox jumps over the lazy dog"));
+
+        assert_eq!("SHA256".parse(), Ok(ETagAlgorithm::Sha256));
+        assert_eq!("none".parse(), Ok(ETagAlgorithm::None));
+        assert!("crc32".parse::<ETagAlgorithm>().is_err());
+    }
+
+    #[test]
+    fn test_uniform_part_size() {
+        assert_eq!(uniform_part_size(&[]), None);
+        assert_eq!(uniform_part_size(&[7]), Some(7));
+        assert_eq!(uniform_part_size(&[5, 5, 2]), Some(5));
+        assert_eq!(uniform_part_size(&[5, 4, 5]), None);
+    }
+
+    #[tokio::test]
+    async fn test_verify_e_tag() {
+        let data: &[u8] = b"The quick brown fox jumps over the lazy dog";
+        let e_tag = "\"9e107d9d372bb6826bd81d3542a419d6\"";
+        assert_eq!(
+            verify_e_tag(data, e_tag, None).await.unwrap(),
+            ETagCheck::Match
+        );
+        assert!(matches!(
+            verify_e_tag(&b"tampered"[..], e_tag, None).await.unwrap(),
+            ETagCheck::Mismatch { .. }
+        ));
+
+        // A multipart etag is the digest of the part digests, so it needs the part boundaries.
+        let mut composite = ETagAlgorithm::Md5.hasher();
+        for part in data.chunks(16) {
+            let mut hasher = ETagAlgorithm::Md5.hasher();
+            hasher.update(part);
+            composite.update(hasher.finalize());
+        }
+        let e_tag = format!("\"{}-3\"", crate::utils::hex(composite.finalize()));
+        assert_eq!(
+            verify_e_tag(data, &e_tag, Some(16)).await.unwrap(),
+            ETagCheck::Match
+        );
+        assert!(matches!(
+            verify_e_tag(data, &e_tag, Some(20)).await.unwrap(),
+            ETagCheck::Mismatch { .. }
+        ));
+        assert_eq!(
+            verify_e_tag(data, &e_tag, None).await.unwrap(),
+            ETagCheck::Unverifiable("part size not recorded")
+        );
+
+        let e_tag = ETagAlgorithm::None.hasher().e_tag();
+        assert_eq!(
+            verify_e_tag(data, &e_tag, None).await.unwrap(),
+            ETagCheck::Unverifiable("etag wasn't computed from the content")
+        );
+    }
+}
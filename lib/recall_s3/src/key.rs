@@ -0,0 +1,84 @@
+use s3s::{s3_error, S3Result};
+
+use crate::recall::UPLOAD_PARTS_PREFIX;
+
+/// Longest object key S3 accepts, in bytes of UTF-8.
+pub const MAX_KEY_LEN: usize = 1024;
+
+/// Whether `c` is one of the characters S3 documents as safe in keys: ASCII letters and digits,
+/// `/` and ``!-_.*'()``. Keys made only of these need no escaping in URLs, XML or shells.
+fn is_safe_key_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '/' | '!' | '-' | '_' | '.' | '*' | '\'' | '(' | ')')
+}
+
+/// Checks a key objects are written under. Keys S3 would refuse are always rejected, so they
+/// can't later break listings or tools reading the bucket, and so are keys under
+/// [`UPLOAD_PARTS_PREFIX`], which listings hide. With `safe_only`, keys are further restricted to
+/// [`is_safe_key_char`] characters.
+pub fn check_object_key(key: &str, safe_only: bool) -> S3Result<()> {
+    if key.is_empty() {
+        return Err(s3_error!(InvalidArgument, "key must not be empty"));
+    }
+    if key.len() > MAX_KEY_LEN {
+        return Err(s3_error!(
+            InvalidArgument,
+            "key is {} bytes long, more than the {MAX_KEY_LEN} allowed",
+            key.len()
+        ));
+    }
+    if key.contains('\0') {
+        return Err(s3_error!(InvalidArgument, "key must not contain NUL"));
+    }
+    if key.starts_with(UPLOAD_PARTS_PREFIX) {
+        return Err(s3_error!(
+            InvalidArgument,
+            "keys under {UPLOAD_PARTS_PREFIX} are reserved for multipart uploads"
+        ));
+    }
+    if safe_only {
+        if let Some(c) = key.chars().find(|&c| !is_safe_key_char(c)) {
+            return Err(s3_error!(
+                InvalidArgument,
+                "key contains {c:?}, only letters, digits and /!-_.*'() are allowed"
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::key::{check_object_key, MAX_KEY_LEN};
+    use crate::recall::UPLOAD_PARTS_PREFIX;
+    use s3s::S3ErrorCode;
+
+    #[test]
+    fn test_check_object_key() {
+        for key in [
+            "a.txt",
+            "dir/sub dir/ünïcode?.txt",
+            &"a".repeat(MAX_KEY_LEN),
+        ] {
+            assert!(check_object_key(key, false).is_ok(), "{key}");
+        }
+
+        // the limit is in bytes, not characters
+        let too_long = "ü".repeat(MAX_KEY_LEN / 2 + 1);
+        let part = format!("{UPLOAD_PARTS_PREFIX}upload/1");
+        for key in ["", "a\0b", &"a".repeat(MAX_KEY_LEN + 1), &too_long, &part] {
+            let err = check_object_key(key, false).unwrap_err();
+            assert_eq!(*err.code(), S3ErrorCode::InvalidArgument);
+        }
+    }
+
+    #[test]
+    fn test_check_object_key_safe_only() {
+        assert!(check_object_key("dir/file_1-(copy)*.tar.gz!", true).is_ok());
+        assert!(check_object_key("it's", true).is_ok());
+
+        for key in ["a b", "a&b", "ü", "a\\b", "a\nb"] {
+            let err = check_object_key(key, true).unwrap_err();
+            assert_eq!(*err.code(), S3ErrorCode::InvalidArgument);
+        }
+    }
+}
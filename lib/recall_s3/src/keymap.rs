@@ -0,0 +1,73 @@
+/// Maps object names to the keys they're stored under in the backend, e.g. to shard a large
+/// bucket by hashing a prefix onto each key. It's applied on every read and write, and reversed
+/// for listings.
+///
+/// Trade-offs to keep in mind:
+/// - Listings are ordered by backend key, so a mapping that doesn't preserve order returns
+///   objects out of name order, and continuation tokens are backend keys.
+/// - Listing by prefix needs the prefix to map onto a backend prefix. Mappings that can't do
+///   that (most hashing schemes) only support unprefixed listings.
+/// - Delimiters group backend keys, so they only make sense for prefix-preserving mappings.
+/// - Changing the mapping of a bucket that already has objects hides those objects.
+pub trait KeyMapping: Send + Sync {
+    fn to_backend(&self, bucket: &str, key: &str) -> String;
+
+    fn from_backend(&self, bucket: &str, key: &[u8]) -> Vec<u8>;
+
+    /// Backend prefix covering every key that starts with `prefix`, or `None` if there's no
+    /// such prefix.
+    fn backend_prefix(&self, _bucket: &str, _prefix: &str) -> Option<String> {
+        None
+    }
+}
+
+/// Stores objects under their own names.
+pub struct IdentityKeyMapping;
+
+impl KeyMapping for IdentityKeyMapping {
+    fn to_backend(&self, _bucket: &str, key: &str) -> String {
+        key.to_string()
+    }
+
+    fn from_backend(&self, _bucket: &str, key: &[u8]) -> Vec<u8> {
+        key.to_vec()
+    }
+
+    fn backend_prefix(&self, _bucket: &str, prefix: &str) -> Option<String> {
+        Some(prefix.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::keymap::{IdentityKeyMapping, KeyMapping};
+
+    struct Sharded;
+
+    impl KeyMapping for Sharded {
+        fn to_backend(&self, _: &str, key: &str) -> String {
+            format!("{:02x}/{key}", key.bytes().fold(0u8, u8::wrapping_add))
+        }
+
+        fn from_backend(&self, _: &str, key: &[u8]) -> Vec<u8> {
+            key[3..].to_vec()
+        }
+    }
+
+    #[test]
+    fn test_key_mapping_round_trip() {
+        let key = "a/b.txt";
+        for mapping in [&IdentityKeyMapping as &dyn KeyMapping, &Sharded] {
+            let backend = mapping.to_backend("foo", key);
+            assert_eq!(
+                mapping.from_backend("foo", backend.as_bytes()),
+                key.as_bytes()
+            );
+        }
+        assert_eq!(
+            IdentityKeyMapping.backend_prefix("foo", "a/"),
+            Some("a/".to_string())
+        );
+        assert_eq!(Sharded.backend_prefix("foo", "a/"), None);
+    }
+}
@@ -12,12 +12,26 @@ clippy::multiple_crate_versions, // TODO: check later
 )]
 
 pub use self::error::*;
-pub use self::recall::Recall;
+pub use self::recall::{Recall, RecallConfig};
 
 #[macro_use]
 mod error;
 
+pub mod balance;
+pub mod clock;
+pub mod compress;
+pub mod error_context;
+pub mod etag;
+pub mod keymap;
+pub mod machines;
+pub mod quota;
+pub mod transform;
+
 mod bucket;
+mod key;
+mod metadata;
+mod missing;
 mod recall;
 mod s3;
+mod upload;
 mod utils;
@@ -0,0 +1,155 @@
+use std::collections::{HashMap, HashSet};
+
+use async_trait::async_trait;
+use recall_provider::fvm_shared::address::Address;
+use recall_provider::json_rpc::JsonRpcProvider;
+use recall_provider::query::FvmQueryHeight;
+use recall_provider::Client;
+use recall_sdk::machine::bucket::Bucket;
+use recall_signer::Void;
+use serde::Serialize;
+
+use crate::bucket::check_bucket_name;
+use crate::metadata::ALIAS_METADATA_KEY;
+
+/// A machine owned by an account, as listed by the chain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnedMachine {
+    pub address: Address,
+    pub metadata: HashMap<String, String>,
+}
+
+/// Where an account's machines are listed from: the chain, through the provider.
+#[async_trait]
+pub trait MachineSource: Send + Sync {
+    async fn owned_machines(&self, owner: Address) -> anyhow::Result<Vec<OwnedMachine>>;
+}
+
+#[async_trait]
+impl<C: Client + Send + Sync> MachineSource for JsonRpcProvider<C> {
+    async fn owned_machines(&self, owner: Address) -> anyhow::Result<Vec<OwnedMachine>> {
+        let list = Bucket::list(self, &Void::new(owner), FvmQueryHeight::Committed).await?;
+        Ok(list
+            .into_iter()
+            .map(|item| OwnedMachine {
+                address: item.address,
+                metadata: item.metadata,
+            })
+            .collect())
+    }
+}
+
+/// One line of [`machines_report`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MachineEntry {
+    pub address: String,
+    pub alias: Option<String>,
+    /// Whether the gateway finds the bucket by its alias. It doesn't if the alias is missing or
+    /// not a valid bucket name, or if an earlier machine already has it.
+    pub visible: bool,
+    pub metadata: HashMap<String, String>,
+}
+
+/// Every machine the account owns, whether or not the gateway can find it by name, as
+/// pretty-printed JSON. Buckets are looked up by their `alias` metadata, so one without a usable
+/// alias is otherwise invisible.
+pub async fn machines_report(source: &dyn MachineSource, owner: Address) -> anyhow::Result<String> {
+    let machines = source.owned_machines(owner).await?;
+
+    // Lookups take the first machine with a matching alias, as listed.
+    let mut seen = HashSet::new();
+    let entries: Vec<MachineEntry> = machines
+        .into_iter()
+        .map(|machine| {
+            let alias = machine.metadata.get(ALIAS_METADATA_KEY).cloned();
+            let visible = alias
+                .as_ref()
+                .is_some_and(|alias| check_bucket_name(alias) && seen.insert(alias.clone()));
+            MachineEntry {
+                address: machine.address.to_string(),
+                alias,
+                visible,
+                metadata: machine.metadata,
+            }
+        })
+        .collect();
+    Ok(serde_json::to_string_pretty(&entries)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::machines::{machines_report, MachineSource, OwnedMachine};
+    use crate::metadata::{ALIAS_METADATA_KEY, CREATION_DATE_METADATA_KEY};
+    use async_trait::async_trait;
+    use recall_provider::fvm_shared::address::Address;
+
+    /// Lists the same machines for every owner.
+    struct FixedMachines(Vec<OwnedMachine>);
+
+    #[async_trait]
+    impl MachineSource for FixedMachines {
+        async fn owned_machines(&self, _: Address) -> anyhow::Result<Vec<OwnedMachine>> {
+            Ok(self.0.clone())
+        }
+    }
+
+    fn machine(id: u64, metadata: &[(&str, &str)]) -> OwnedMachine {
+        OwnedMachine {
+            address: Address::new_id(id),
+            metadata: metadata
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_machines_report() {
+        let source = FixedMachines(vec![
+            machine(
+                1,
+                &[
+                    (ALIAS_METADATA_KEY, "foo"),
+                    (CREATION_DATE_METADATA_KEY, "1"),
+                ],
+            ),
+            machine(2, &[(CREATION_DATE_METADATA_KEY, "2")]),
+            machine(3, &[(ALIAS_METADATA_KEY, "foo")]),
+            machine(4, &[(ALIAS_METADATA_KEY, "Not A Bucket")]),
+        ]);
+
+        let report = machines_report(&source, Address::new_id(100))
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_str(&report).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([
+                {
+                    "address": Address::new_id(1).to_string(),
+                    "alias": "foo",
+                    "visible": true,
+                    "metadata": {"alias": "foo", "creation_date": "1"},
+                },
+                {
+                    "address": Address::new_id(2).to_string(),
+                    "alias": null,
+                    "visible": false,
+                    "metadata": {"creation_date": "2"},
+                },
+                {
+                    "address": Address::new_id(3).to_string(),
+                    "alias": "foo",
+                    "visible": false,
+                    "metadata": {"alias": "foo"},
+                },
+                {
+                    "address": Address::new_id(4).to_string(),
+                    "alias": "Not A Bucket",
+                    "visible": false,
+                    "metadata": {"alias": "Not A Bucket"},
+                },
+            ])
+        );
+    }
+}
@@ -0,0 +1,220 @@
+use std::collections::HashMap;
+
+use s3s::dto::Metadata;
+
+use crate::compress::StorageCompression;
+
+pub static LAST_MODIFIED_METADATA_KEY: &str = "last_modified";
+pub static CREATION_DATE_METADATA_KEY: &str = "creation_date";
+pub static ETAG_METADATA_KEY: &str = "etag";
+pub static ALIAS_METADATA_KEY: &str = "alias";
+/// Logical length of an object whose stored bytes are encoded, e.g. encrypted or compressed.
+pub static PLAINTEXT_LENGTH_METADATA_KEY: &str = "plaintext_length";
+/// How the stored bytes are compressed, if the writer asked for it.
+pub static COMPRESSION_METADATA_KEY: &str = "compression";
+pub static CONTENT_TYPE_METADATA_KEY: &str = "content_type";
+/// Size of every part but the last of a multipart object, so its etag can be recomputed. Only
+/// recorded when those parts are all the same size.
+pub static PART_SIZE_METADATA_KEY: &str = "part_size";
+/// Object lock retention: its mode and the epoch second it lasts until.
+pub static RETENTION_MODE_METADATA_KEY: &str = "retention_mode";
+pub static RETAIN_UNTIL_METADATA_KEY: &str = "retain_until";
+/// Present, as `ON`, while the object is under a legal hold.
+pub static LEGAL_HOLD_METADATA_KEY: &str = "legal_hold";
+pub static STORAGE_CLASS_METADATA_KEY: &str = "storage_class";
+/// Access key whose quota the stored bytes are charged to, so whoever deletes the object gives
+/// them back to that key. Only recorded while quotas are enforced.
+pub static QUOTA_OWNER_METADATA_KEY: &str = "quota_owner";
+
+/// Metadata keys written by the gateway itself. Users can't set them, and they're never
+/// returned as user metadata.
+pub const RESERVED_METADATA_KEYS: &[&str] = &[
+    LAST_MODIFIED_METADATA_KEY,
+    ETAG_METADATA_KEY,
+    CREATION_DATE_METADATA_KEY,
+    ALIAS_METADATA_KEY,
+    PLAINTEXT_LENGTH_METADATA_KEY,
+    COMPRESSION_METADATA_KEY,
+    CONTENT_TYPE_METADATA_KEY,
+    PART_SIZE_METADATA_KEY,
+    RETENTION_MODE_METADATA_KEY,
+    RETAIN_UNTIL_METADATA_KEY,
+    LEGAL_HOLD_METADATA_KEY,
+    STORAGE_CLASS_METADATA_KEY,
+    QUOTA_OWNER_METADATA_KEY,
+];
+
+/// Reserved keys recording an object's lock. A lock belongs to the object it was set on, so
+/// these aren't carried over to copies.
+pub const LOCK_METADATA_KEYS: &[&str] = &[
+    RETENTION_MODE_METADATA_KEY,
+    RETAIN_UNTIL_METADATA_KEY,
+    LEGAL_HOLD_METADATA_KEY,
+];
+
+/// Prefix of keys reserved for server-side encryption state.
+const RESERVED_METADATA_PREFIX: &str = "sse_";
+
+pub fn is_reserved(key: &str) -> bool {
+    let key = key.to_ascii_lowercase();
+    RESERVED_METADATA_KEYS.contains(&key.as_str()) || key.starts_with(RESERVED_METADATA_PREFIX)
+}
+
+/// Length of the object as clients see it. Falls back to the stored size for objects written
+/// without a recorded plaintext length.
+pub fn logical_size(stored_size: u64, metadata: &HashMap<String, String>) -> u64 {
+    metadata
+        .get(PLAINTEXT_LENGTH_METADATA_KEY)
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(stored_size)
+}
+
+/// Compression the object is stored with, undone when it's read.
+pub fn compression(metadata: &HashMap<String, String>) -> Option<StorageCompression> {
+    metadata
+        .get(COMPRESSION_METADATA_KEY)
+        .and_then(|v| v.parse().ok())
+}
+
+/// Content type the object was stored with. Objects stored without one are binary.
+pub fn content_type(metadata: &HashMap<String, String>) -> mime::Mime {
+    metadata
+        .get(CONTENT_TYPE_METADATA_KEY)
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(mime::APPLICATION_OCTET_STREAM)
+}
+
+/// Storage class the object was stored with. Objects stored before it was recorded are in
+/// `default`, as the backend has a single durability tier.
+pub fn storage_class<'a>(metadata: &'a HashMap<String, String>, default: &'a str) -> &'a str {
+    metadata
+        .get(STORAGE_CLASS_METADATA_KEY)
+        .map_or(default, String::as_str)
+}
+
+/// Retention mode and end the object was locked with, as long as it hasn't run out at `now`
+/// (epoch seconds).
+pub fn active_retention(metadata: &HashMap<String, String>, now: u64) -> Option<(&str, u64)> {
+    let mode = metadata.get(RETENTION_MODE_METADATA_KEY)?;
+    let until = metadata.get(RETAIN_UNTIL_METADATA_KEY)?.parse().ok()?;
+    (until > now).then_some((mode.as_str(), until))
+}
+
+/// Whether the object is under a legal hold.
+pub fn legal_hold(metadata: &HashMap<String, String>) -> bool {
+    metadata
+        .get(LEGAL_HOLD_METADATA_KEY)
+        .is_some_and(|v| v == "ON")
+}
+
+/// The user-supplied part of an object's stored metadata, if any.
+pub fn user_metadata(metadata: &HashMap<String, String>) -> Option<Metadata> {
+    let user: Metadata = metadata
+        .iter()
+        .filter(|(k, _)| !is_reserved(k))
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect();
+    (!user.is_empty()).then_some(user)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compress::StorageCompression;
+    use crate::metadata::{
+        active_retention, compression, content_type, is_reserved, legal_hold, logical_size,
+        storage_class, user_metadata, COMPRESSION_METADATA_KEY, CONTENT_TYPE_METADATA_KEY,
+        ETAG_METADATA_KEY, LEGAL_HOLD_METADATA_KEY, PLAINTEXT_LENGTH_METADATA_KEY,
+        RETAIN_UNTIL_METADATA_KEY, RETENTION_MODE_METADATA_KEY, STORAGE_CLASS_METADATA_KEY,
+    };
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_is_reserved() {
+        assert!(is_reserved("etag"));
+        assert!(is_reserved("Last_Modified"));
+        assert!(is_reserved("sse_key"));
+        assert!(!is_reserved("author"));
+        assert!(!is_reserved("etags"));
+    }
+
+    #[test]
+    fn test_user_metadata() {
+        let metadata = HashMap::from([
+            (ETAG_METADATA_KEY.to_string(), "\"abc\"".to_string()),
+            ("author".to_string(), "me".to_string()),
+        ]);
+        let user = user_metadata(&metadata).unwrap();
+        assert_eq!(user.len(), 1);
+        assert_eq!(user.get("author").unwrap(), "me");
+
+        assert!(user_metadata(&HashMap::new()).is_none());
+    }
+
+    #[test]
+    fn test_logical_size() {
+        // Plain objects report their stored size.
+        assert_eq!(logical_size(42, &HashMap::new()), 42);
+
+        // Encoded objects report the recorded plaintext length.
+        let metadata =
+            HashMap::from([(PLAINTEXT_LENGTH_METADATA_KEY.to_string(), "32".to_string())]);
+        assert_eq!(logical_size(42, &metadata), 32);
+        assert!(is_reserved(PLAINTEXT_LENGTH_METADATA_KEY));
+    }
+
+    #[test]
+    fn test_compression() {
+        assert_eq!(compression(&HashMap::new()), None);
+
+        let metadata = HashMap::from([(COMPRESSION_METADATA_KEY.to_string(), "zstd".to_string())]);
+        assert_eq!(compression(&metadata), Some(StorageCompression::Zstd));
+        assert!(user_metadata(&metadata).is_none());
+    }
+
+    #[test]
+    fn test_content_type() {
+        assert_eq!(
+            content_type(&HashMap::new()),
+            mime::APPLICATION_OCTET_STREAM
+        );
+
+        let metadata = HashMap::from([(
+            CONTENT_TYPE_METADATA_KEY.to_string(),
+            "text/plain".to_string(),
+        )]);
+        assert_eq!(content_type(&metadata), mime::TEXT_PLAIN);
+        assert!(user_metadata(&metadata).is_none());
+    }
+
+    #[test]
+    fn test_storage_class() {
+        assert_eq!(storage_class(&HashMap::new(), "STANDARD"), "STANDARD");
+
+        let metadata = HashMap::from([(
+            STORAGE_CLASS_METADATA_KEY.to_string(),
+            "ONEZONE_IA".to_string(),
+        )]);
+        assert_eq!(storage_class(&metadata, "STANDARD"), "ONEZONE_IA");
+        assert!(user_metadata(&metadata).is_none());
+    }
+
+    #[test]
+    fn test_object_lock() {
+        assert!(active_retention(&HashMap::new(), 100).is_none());
+        assert!(!legal_hold(&HashMap::new()));
+
+        let metadata = HashMap::from([
+            (
+                RETENTION_MODE_METADATA_KEY.to_string(),
+                "GOVERNANCE".to_string(),
+            ),
+            (RETAIN_UNTIL_METADATA_KEY.to_string(), "200".to_string()),
+            (LEGAL_HOLD_METADATA_KEY.to_string(), "ON".to_string()),
+        ]);
+        assert_eq!(active_retention(&metadata, 100), Some(("GOVERNANCE", 200)));
+        // Retention that ran out no longer locks the object.
+        assert!(active_retention(&metadata, 200).is_none());
+        assert!(legal_hold(&metadata));
+        assert!(user_metadata(&metadata).is_none());
+    }
+}
@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use recall_provider::fvm_shared::address::Address;
+
+/// Most keys remembered at once, so a client scanning for random keys can't grow the cache
+/// without bound. Misses beyond it are simply not cached.
+const MAX_MISSING_KEYS: usize = 10_000;
+
+/// Backend keys recently found absent, per bucket address, so repeated lookups of a missing key
+/// are answered without querying the backend.
+///
+/// Entries live for `ttl` at most. Writes through this gateway forget the key right away; writes
+/// through anything else are only seen once the entry expires, which is why the TTL should be
+/// short.
+pub struct MissingKeys {
+    ttl: Duration,
+    entries: Mutex<HashMap<(Address, String), SystemTime>>,
+}
+
+impl MissingKeys {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::default(),
+        }
+    }
+
+    /// Whether the key was found absent less than `ttl` before `now`.
+    pub fn contains(&self, address: Address, key: &str, now: SystemTime) -> bool {
+        let mut entries = self.entries.lock().expect("missing keys lock poisoned");
+        let entry = (address, key.to_string());
+        match entries.get(&entry) {
+            Some(expires_at) if *expires_at > now => true,
+            Some(_) => {
+                entries.remove(&entry);
+                false
+            }
+            None => false,
+        }
+    }
+
+    pub fn insert(&self, address: Address, key: &str, now: SystemTime) {
+        let mut entries = self.entries.lock().expect("missing keys lock poisoned");
+        if entries.len() >= MAX_MISSING_KEYS {
+            entries.retain(|_, expires_at| *expires_at > now);
+            if entries.len() >= MAX_MISSING_KEYS {
+                return;
+            }
+        }
+        entries.insert((address, key.to_string()), now + self.ttl);
+    }
+
+    /// Forgets a key that was just written.
+    pub fn remove(&self, address: Address, key: &str) {
+        self.entries
+            .lock()
+            .expect("missing keys lock poisoned")
+            .remove(&(address, key.to_string()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::missing::{MissingKeys, MAX_MISSING_KEYS};
+    use recall_provider::fvm_shared::address::Address;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn test_missing_keys() {
+        let missing = MissingKeys::new(Duration::from_secs(2));
+        let (a, b) = (Address::new_id(1), Address::new_id(2));
+        let now = UNIX_EPOCH + Duration::from_secs(100);
+
+        missing.insert(a, "foo", now);
+        assert!(missing.contains(a, "foo", now + Duration::from_secs(1)));
+        // misses are per bucket
+        assert!(!missing.contains(b, "foo", now));
+        assert!(!missing.contains(a, "foo", now + Duration::from_secs(2)));
+
+        missing.insert(a, "foo", now);
+        missing.remove(a, "foo");
+        assert!(!missing.contains(a, "foo", now));
+    }
+
+    #[test]
+    fn test_missing_keys_bounded() {
+        let missing = MissingKeys::new(Duration::from_secs(2));
+        let address = Address::new_id(1);
+        let now = UNIX_EPOCH;
+
+        for i in 0..MAX_MISSING_KEYS {
+            missing.insert(address, &i.to_string(), now);
+        }
+        missing.insert(address, "full", now);
+        assert!(!missing.contains(address, "full", now));
+
+        // expired entries make room
+        let later = now + Duration::from_secs(2);
+        missing.insert(address, "full", later);
+        assert!(missing.contains(address, "full", later));
+    }
+}
@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Bytes stored per access key, checked against a limit before each write.
+///
+/// Usage is in stored bytes. Writes reserve their size before they're committed and give it
+/// back if they fail; deletes and overwrites give back the size of the object they remove.
+pub trait QuotaStore: Send + Sync {
+    /// Most bytes the access key may store, or `None` if it isn't limited.
+    fn limit(&self, access_key: &str) -> Option<u64>;
+
+    /// Bytes the access key currently stores, including reservations.
+    fn usage(&self, access_key: &str) -> u64;
+
+    /// Adds `bytes` to the access key's usage unless that would exceed its limit.
+    fn try_reserve(&self, access_key: &str, bytes: u64) -> bool;
+
+    /// Takes `bytes` off the access key's usage.
+    fn release(&self, access_key: &str, bytes: u64);
+
+    /// Whether `bytes` more would still fit, without reserving them.
+    fn allows(&self, access_key: &str, bytes: u64) -> bool {
+        self.limit(access_key)
+            .is_none_or(|limit| self.usage(access_key).saturating_add(bytes) <= limit)
+    }
+}
+
+/// Quotas kept in memory. Usage starts at zero, so it only covers what was written since the
+/// process started.
+#[derive(Default)]
+pub struct InMemoryQuotaStore {
+    limits: HashMap<String, u64>,
+    usage: Mutex<HashMap<String, u64>>,
+}
+
+impl InMemoryQuotaStore {
+    pub fn new(limits: HashMap<String, u64>) -> Self {
+        Self {
+            limits,
+            usage: Mutex::default(),
+        }
+    }
+}
+
+impl QuotaStore for InMemoryQuotaStore {
+    fn limit(&self, access_key: &str) -> Option<u64> {
+        self.limits.get(access_key).copied()
+    }
+
+    fn usage(&self, access_key: &str) -> u64 {
+        let usage = self.usage.lock().expect("quota usage lock poisoned");
+        usage.get(access_key).copied().unwrap_or_default()
+    }
+
+    fn try_reserve(&self, access_key: &str, bytes: u64) -> bool {
+        let mut usage = self.usage.lock().expect("quota usage lock poisoned");
+        let used = usage.entry(access_key.to_string()).or_default();
+        let reserved = used.saturating_add(bytes);
+        if self.limit(access_key).is_some_and(|limit| reserved > limit) {
+            return false;
+        }
+        *used = reserved;
+        true
+    }
+
+    fn release(&self, access_key: &str, bytes: u64) {
+        let mut usage = self.usage.lock().expect("quota usage lock poisoned");
+        if let Some(used) = usage.get_mut(access_key) {
+            *used = used.saturating_sub(bytes);
+        }
+    }
+}
+
+/// A store shared by several servers, e.g. one per virtual-host domain.
+impl<T: QuotaStore + ?Sized> QuotaStore for Arc<T> {
+    fn limit(&self, access_key: &str) -> Option<u64> {
+        (**self).limit(access_key)
+    }
+
+    fn usage(&self, access_key: &str) -> u64 {
+        (**self).usage(access_key)
+    }
+
+    fn try_reserve(&self, access_key: &str, bytes: u64) -> bool {
+        (**self).try_reserve(access_key, bytes)
+    }
+
+    fn release(&self, access_key: &str, bytes: u64) {
+        (**self).release(access_key, bytes);
+    }
+}
+
+/// Quota reserved for a write in flight. It's released on drop unless the write is committed.
+pub struct QuotaReservation<'a> {
+    store: &'a dyn QuotaStore,
+    access_key: String,
+    bytes: u64,
+    committed: bool,
+}
+
+impl<'a> QuotaReservation<'a> {
+    /// Reserves `bytes` for the access key, or returns `None` if they don't fit its quota.
+    pub fn new(store: &'a dyn QuotaStore, access_key: &str, bytes: u64) -> Option<Self> {
+        store.try_reserve(access_key, bytes).then(|| Self {
+            store,
+            access_key: access_key.to_string(),
+            bytes,
+            committed: false,
+        })
+    }
+
+    /// Access key the bytes are reserved for.
+    pub fn access_key(&self) -> &str {
+        &self.access_key
+    }
+
+    /// Keeps the reserved bytes counted, once the write landed.
+    pub fn commit(mut self) {
+        self.committed = true;
+    }
+}
+
+impl Drop for QuotaReservation<'_> {
+    fn drop(&mut self) {
+        if !self.committed {
+            self.store.release(&self.access_key, self.bytes);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::quota::{InMemoryQuotaStore, QuotaReservation, QuotaStore};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_in_memory_quota() {
+        let store = InMemoryQuotaStore::new(HashMap::from([("tenant".to_string(), 100)]));
+
+        assert!(store.try_reserve("tenant", 60));
+        assert!(!store.try_reserve("tenant", 50));
+        assert!(store.allows("tenant", 40));
+        assert!(!store.allows("tenant", 41));
+
+        store.release("tenant", 60);
+        assert_eq!(store.usage("tenant"), 0);
+
+        // Access keys without a limit are tracked but never refused.
+        assert!(store.try_reserve("admin", u64::MAX));
+        assert_eq!(store.usage("admin"), u64::MAX);
+    }
+
+    #[test]
+    fn test_reservation_released_unless_committed() {
+        let store = InMemoryQuotaStore::new(HashMap::from([("tenant".to_string(), 100)]));
+
+        let failed = QuotaReservation::new(&store, "tenant", 80).unwrap();
+        assert!(QuotaReservation::new(&store, "tenant", 80).is_none());
+        drop(failed);
+        assert_eq!(store.usage("tenant"), 0);
+
+        QuotaReservation::new(&store, "tenant", 80)
+            .unwrap()
+            .commit();
+        assert_eq!(store.usage("tenant"), 80);
+        assert!(QuotaReservation::new(&store, "tenant", 80).is_none());
+    }
+}
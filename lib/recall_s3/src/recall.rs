@@ -1,25 +1,181 @@
+use std::collections::{HashMap, HashSet};
 use std::ops::Deref;
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use crate::bucket::{split_eth_address, BucketNameWithOwner};
+use crate::bucket::{
+    check_owner_prefix, invalid_bucket_path, split_eth_address, BucketNameWithOwner,
+};
+use crate::clock::{Clock, SystemClock};
+use crate::etag::{verify_e_tag, ETagAlgorithm, ETagCheck};
+use crate::keymap::{IdentityKeyMapping, KeyMapping};
+use crate::metadata::{
+    ALIAS_METADATA_KEY, CREATION_DATE_METADATA_KEY, ETAG_METADATA_KEY, PART_SIZE_METADATA_KEY,
+    PLAINTEXT_LENGTH_METADATA_KEY,
+};
+use crate::missing::MissingKeys;
+use crate::quota::QuotaStore;
+use crate::transform::ObjectTransform;
+use crate::upload::UploadManifest;
+use crate::utils::{hex, ExactLengthReader, HashReader};
+use anyhow::anyhow;
 use bytestring::ByteString;
 use ethers::utils::hex::ToHexExt;
 use recall_provider::{
-    fvm_shared::address::Address, json_rpc::JsonRpcProvider, query::FvmQueryHeight, Client,
+    fvm_shared::address::Address, json_rpc::JsonRpcProvider, message::GasParams,
+    query::FvmQueryHeight, Client,
 };
-use recall_sdk::machine::bucket::{Bucket, ObjectState, QueryOptions};
+use recall_sdk::machine::bucket::{Bucket, GetOptions, ObjectState, QueryOptions};
 use recall_sdk::machine::Machine;
 use recall_signer::{Signer, Void};
-use s3s::dto::{BucketName, ObjectKey, PartNumber};
+use s3s::auth::Credentials;
+use s3s::dto::{BucketName, CompletedPart, ObjectKey, PartNumber, StorageClass};
 use s3s::{s3_error, S3Error, S3ErrorCode};
+use tokio::fs;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tracing::warn;
 use uuid::Uuid;
 
+/// Bytes in flight between the download and the hashing of an object being verified.
+const VERIFY_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Bytes buffered between an object's download and its response body, by default.
+pub const DEFAULT_DOWNLOAD_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Backend key prefix of multipart parts stored in the upload's bucket. Listings hide it.
+pub const UPLOAD_PARTS_PREFIX: &str = ".recall-s3-uploads/";
+
+/// How long bucket stats are reused before the bucket is listed again.
+const BUCKET_STATS_TTL: Duration = Duration::from_secs(30);
+
+/// Objects in a bucket and the bytes they're stored in.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BucketStats {
+    pub objects: u64,
+    pub bytes: u64,
+}
+
 pub struct Recall<C: Client + Send + Sync, S: Signer> {
     pub root: PathBuf,
+    /// Scratch space for buffered uploads and multipart parts. Defaults to `root`.
+    pub temp_dir: PathBuf,
     pub provider: Arc<JsonRpcProvider<C>>,
     pub wallet: Option<S>,
     pub is_read_only: bool,
+    /// Region reported to clients. Bucket location constraints are only validated when set.
+    pub region: Option<String>,
+    pub metrics_per_bucket: bool,
+    /// Ceiling on `max-keys` in listings. Defaults to S3's 1000.
+    pub max_keys_hard_limit: u64,
+    /// Applied in order to object bodies on `get_object`.
+    pub transforms: Vec<Box<dyn ObjectTransform>>,
+    /// Maps object names to backend keys. Defaults to the identity.
+    pub key_mapping: Box<dyn KeyMapping>,
+    /// Timestamps recorded on buckets and objects. Defaults to the system clock.
+    pub clock: Box<dyn Clock>,
+    /// Key prefix each access key is confined to, so tenants can share buckets. Access keys
+    /// without an entry see whole buckets.
+    pub key_prefixes: HashMap<String, String>,
+    pub etag_algorithm: ETagAlgorithm,
+    /// Refuse to start multipart uploads, so no parts are ever buffered on disk. Clients have to
+    /// upload objects, however large, with a single PutObject.
+    pub disable_multipart: bool,
+    /// Longest a blob upload or download through the object API may take. Unbounded if unset.
+    pub object_api_timeout: Option<Duration>,
+    /// Storage class objects are stored with and reported in. The backend has one durability
+    /// tier, so requests for any other class are refused. Defaults to `STANDARD`.
+    pub storage_class: String,
+    /// Bytes buffered between an object's download and its response body. The download waits
+    /// while the buffer is full, so a slow client holds at most this much per request.
+    pub download_buffer_size: usize,
+    /// Most parts a multipart upload may have, e.g. S3's 10,000. Unlimited when unset.
+    pub max_parts: Option<u32>,
+    /// Least size of every part but the last, e.g. S3's 5 MiB. Unchecked when unset.
+    pub min_part_size: Option<u64>,
+    /// Only accept keys made of the characters S3 documents as safe for new objects. Keys are
+    /// always held to S3's length limit and may never contain NUL.
+    pub safe_object_keys: bool,
+    /// Store multipart parts as hidden objects in the upload's bucket rather than in `temp_dir`,
+    /// so no part of an upload is ever on local disk. Completing the upload streams the parts
+    /// back through the gateway into the object, and then deletes them.
+    pub multipart_parts_in_backend: bool,
+    /// Caps the bytes each access key may store. Unlimited when unset.
+    pub quota: Option<Box<dyn QuotaStore>>,
+    /// Keys recently found absent, so repeated 404s skip the backend. Off unless a TTL is set.
+    pub(crate) missing_keys: Option<Arc<MissingKeys>>,
+    pending_creates: Arc<Mutex<HashSet<(Address, String)>>>,
+    /// Stats of recently counted buckets and backend key prefixes, with when they were counted.
+    pub(crate) bucket_stats: Mutex<HashMap<(Address, String), (SystemTime, BucketStats)>>,
+    /// Serializes read-modify-write updates of upload manifests.
+    pub(crate) upload_manifests: Arc<tokio::sync::Mutex<()>>,
+}
+
+/// Per-server settings for [`Recall::with_config`]. New settings are added here, so adding one
+/// doesn't change any constructor's signature.
+#[derive(Clone, Debug)]
+pub struct RecallConfig {
+    /// Scratch space for buffered uploads and multipart parts. Defaults to the data root.
+    pub temp_dir: Option<PathBuf>,
+    /// Region reported to clients. Bucket location constraints are only validated when set.
+    pub region: Option<String>,
+    pub metrics_per_bucket: bool,
+    /// Ceiling on `max-keys` in listings. Defaults to S3's 1000.
+    pub max_keys_hard_limit: u64,
+    /// Key prefix each access key is confined to.
+    pub key_prefixes: HashMap<String, String>,
+    pub etag_algorithm: ETagAlgorithm,
+    pub disable_multipart: bool,
+    /// Longest a blob upload or download through the object API may take. Unbounded if unset.
+    pub object_api_timeout: Option<Duration>,
+    pub multipart_parts_in_backend: bool,
+    pub storage_class: String,
+    pub download_buffer_size: usize,
+    pub max_parts: Option<u32>,
+    pub min_part_size: Option<u64>,
+    /// How long a key found absent is answered with `NoSuchKey` without asking the backend.
+    /// Writes through another gateway go unseen for up to this long. Misses aren't cached if unset.
+    pub missing_key_ttl: Option<Duration>,
+    /// Only accept keys made of the characters S3 documents as safe for new objects.
+    pub safe_object_keys: bool,
+}
+
+impl Default for RecallConfig {
+    fn default() -> Self {
+        Self {
+            temp_dir: None,
+            region: None,
+            metrics_per_bucket: false,
+            max_keys_hard_limit: crate::s3::MAX_LIST_OBJECTS_KEYS,
+            key_prefixes: HashMap::new(),
+            etag_algorithm: ETagAlgorithm::default(),
+            disable_multipart: false,
+            object_api_timeout: None,
+            multipart_parts_in_backend: false,
+            storage_class: StorageClass::STANDARD.to_string(),
+            download_buffer_size: DEFAULT_DOWNLOAD_BUFFER_SIZE,
+            max_parts: None,
+            min_part_size: None,
+            missing_key_ttl: None,
+            safe_object_keys: false,
+        }
+    }
+}
+
+/// An in-flight create-only write of a key, released on drop.
+pub struct CreateClaim<'a> {
+    pending: &'a Mutex<HashSet<(Address, String)>>,
+    entry: (Address, String),
+}
+
+impl Drop for CreateClaim<'_> {
+    fn drop(&mut self) {
+        self.pending
+            .lock()
+            .expect("pending creates lock poisoned")
+            .remove(&self.entry);
+    }
 }
 
 impl<C, S> Recall<C, S>
@@ -31,30 +187,383 @@ where
         root: PathBuf,
         provider: JsonRpcProvider<C>,
         wallet: Option<S>,
+    ) -> anyhow::Result<Self> {
+        Self::with_config(root, provider, wallet, RecallConfig::default())
+    }
+
+    pub fn with_config(
+        root: PathBuf,
+        provider: JsonRpcProvider<C>,
+        wallet: Option<S>,
+        config: RecallConfig,
     ) -> anyhow::Result<Self> {
         let is_read_only = wallet.is_none();
         Ok(Self {
+            temp_dir: config.temp_dir.unwrap_or_else(|| root.clone()),
             root,
             wallet,
             is_read_only,
+            region: config.region,
+            metrics_per_bucket: config.metrics_per_bucket,
+            max_keys_hard_limit: config.max_keys_hard_limit,
+            transforms: Vec::new(),
+            key_mapping: Box::new(IdentityKeyMapping),
+            clock: Box::new(SystemClock),
+            key_prefixes: config.key_prefixes,
+            etag_algorithm: config.etag_algorithm,
+            disable_multipart: config.disable_multipart,
+            object_api_timeout: config.object_api_timeout,
+            multipart_parts_in_backend: config.multipart_parts_in_backend,
+            storage_class: config.storage_class,
+            download_buffer_size: config.download_buffer_size,
+            max_parts: config.max_parts,
+            min_part_size: config.min_part_size,
+            safe_object_keys: config.safe_object_keys,
+            quota: None,
+            missing_keys: config
+                .missing_key_ttl
+                .map(|ttl| Arc::new(MissingKeys::new(ttl))),
+            pending_creates: Arc::default(),
+            bucket_stats: Mutex::default(),
+            upload_manifests: Arc::default(),
             provider: Arc::new(provider),
         })
     }
 
+    pub fn add_transform(&mut self, transform: impl ObjectTransform + 'static) {
+        self.transforms.push(Box::new(transform));
+    }
+
+    pub fn set_key_mapping(&mut self, key_mapping: impl KeyMapping + 'static) {
+        self.key_mapping = Box::new(key_mapping);
+    }
+
+    /// Key prefix the credentials are confined to, or an empty string if they aren't.
+    pub fn key_prefix(&self, credentials: Option<&Credentials>) -> &str {
+        credentials
+            .and_then(|c| self.key_prefixes.get(&c.access_key))
+            .map_or("", String::as_str)
+    }
+
+    /// Backend key of an object, within the credentials' namespace. `s3s` percent-decodes the
+    /// request path once before routing, so `key` is used as is and must not be decoded again.
+    pub fn backend_key(
+        &self,
+        credentials: Option<&Credentials>,
+        bucket: &BucketNameWithOwner,
+        key: &str,
+    ) -> String {
+        let key = format!("{}{key}", self.key_prefix(credentials));
+        self.key_mapping.to_backend(&bucket.name(), &key)
+    }
+
     pub fn get_upload_path(&self, upload_id: &Uuid) -> PathBuf {
-        self.root.join(format!("upload-{upload_id}.json"))
+        self.root.join(UploadManifest::file_name(upload_id))
     }
 
     pub fn get_upload_part_path(&self, upload_id: &Uuid, part_number: PartNumber) -> PathBuf {
-        self.root
+        self.temp_dir
             .join(format!(".upload-{upload_id}.part-{part_number}.json"))
     }
 
+    /// Backend key of a part stored with [`Self::multipart_parts_in_backend`].
+    pub fn get_upload_part_key(&self, upload_id: &Uuid, part_number: PartNumber) -> String {
+        format!("{UPLOAD_PARTS_PREFIX}{upload_id}/{part_number}")
+    }
+
+    /// Concatenates the parts of an upload into `writer`, returning the multipart etag and the
+    /// part files used. The parts are left in place so a failed complete can be retried.
+    pub async fn assemble_parts<W>(
+        &self,
+        upload_id: &Uuid,
+        parts: impl IntoIterator<Item = CompletedPart>,
+        writer: &mut W,
+    ) -> Result<(String, Vec<PathBuf>, Vec<u64>), S3Error>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let mut part_paths = Vec::new();
+        let mut part_sizes = Vec::new();
+        let mut e_tag_hash = self.etag_algorithm.hasher();
+        for part in parts {
+            let part_number = next_part_number(&part, part_paths.len())?;
+            let part_path = self.get_upload_part_path(upload_id, part_number);
+            let reader = try_!(fs::File::open(&part_path).await);
+            let mut hash_reader = HashReader::new(reader, self.etag_algorithm.hasher());
+            let size = try_!(tokio::io::copy(&mut hash_reader, writer).await);
+            e_tag_hash.update(hash_reader.finalize());
+            part_paths.push(part_path);
+            part_sizes.push(size);
+        }
+        try_!(writer.flush().await);
+
+        let e_tag = format!("\"{}-{}\"", hex(e_tag_hash.finalize()), part_paths.len());
+        Ok((e_tag, part_paths, part_sizes))
+    }
+
+    /// The multipart etag of an upload whose parts are stored in the backend, and the keys and
+    /// sizes of its parts in order. It's computed from the part etags in the manifest, so the
+    /// parts don't have to be read twice, and matches what [`Self::assemble_parts`] returns.
+    pub fn backend_parts(
+        &self,
+        upload_id: &Uuid,
+        manifest: &UploadManifest,
+        parts: impl IntoIterator<Item = CompletedPart>,
+    ) -> Result<(String, Vec<(String, u64)>), S3Error> {
+        let mut part_keys = Vec::new();
+        let mut e_tag_hash = self.etag_algorithm.hasher();
+        for part in parts {
+            let part_number = next_part_number(&part, part_keys.len())?;
+            let Some(uploaded) = manifest.parts.get(&part_number) else {
+                return Err(s3_error!(InvalidPart, "part {part_number} wasn't uploaded"));
+            };
+            let digest = hex_simd::decode_to_vec(uploaded.e_tag.trim_matches('"'))
+                .map_err(|_| s3_error!(InternalError, "part {part_number} has a malformed etag"))?;
+            e_tag_hash.update(digest);
+            part_keys.push((
+                self.get_upload_part_key(upload_id, part_number),
+                uploaded.size,
+            ));
+        }
+
+        let e_tag = format!("\"{}-{}\"", hex(e_tag_hash.finalize()), part_keys.len());
+        Ok((e_tag, part_keys))
+    }
+
+    /// Refuses a part number beyond [`Self::max_parts`].
+    pub fn check_part_number(&self, part_number: PartNumber) -> Result<(), S3Error> {
+        match self.max_parts {
+            Some(max) if !u32::try_from(part_number).is_ok_and(|n| n <= max) => Err(s3_error!(
+                InvalidRequest,
+                "part number {part_number} is beyond the limit of {max} parts"
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks the sizes of the parts an upload is completed with against [`Self::max_parts`] and
+    /// [`Self::min_part_size`]. The last part may be smaller.
+    pub fn check_part_sizes(&self, part_sizes: &[u64]) -> Result<(), S3Error> {
+        if let Some(max) = self.max_parts {
+            if part_sizes.len() > max as usize {
+                return Err(s3_error!(
+                    InvalidRequest,
+                    "{} parts are more than the limit of {max}",
+                    part_sizes.len()
+                ));
+            }
+        }
+        if let Some(min) = self.min_part_size {
+            let but_last = &part_sizes[..part_sizes.len().saturating_sub(1)];
+            if let Some(n) = but_last.iter().position(|size| *size < min) {
+                return Err(s3_error!(
+                    EntityTooSmall,
+                    "part {} is smaller than the minimum part size of {min} bytes",
+                    n + 1
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Claims the backend `key` in the bucket at `address` for a create-only write. Returns `None`
+    /// if another create-only write of the same key is already in flight through this gateway.
+    pub fn claim_create(&self, address: Address, key: &str) -> Option<CreateClaim<'_>> {
+        let entry = (address, key.to_string());
+        let mut pending = self
+            .pending_creates
+            .lock()
+            .expect("pending creates lock poisoned");
+        if !pending.insert(entry.clone()) {
+            return None;
+        }
+
+        Some(CreateClaim {
+            pending: &self.pending_creates,
+            entry,
+        })
+    }
+
+    /// Coordinates writes with `other`, i.e. create-only puts, upload manifest updates and the
+    /// missing-key cache, so both instances can serve the same buckets side by side.
+    pub fn share_writes_with(&mut self, other: &Self) {
+        self.pending_creates = other.pending_creates.clone();
+        self.upload_manifests = other.upload_manifests.clone();
+        self.missing_keys = other.missing_keys.clone();
+    }
+
+    /// Counts the objects under the backend key `prefix` in the bucket at `address` and the
+    /// bytes they're stored in, paging through their whole listing. That takes a query per
+    /// thousand objects, so the result is reused for a while. Hidden multipart parts aren't
+    /// counted, as listings don't show them.
+    pub async fn bucket_stats(
+        &self,
+        address: Address,
+        prefix: &str,
+    ) -> Result<BucketStats, S3Error> {
+        let now = self.clock.now();
+        let entry = (address, prefix.to_string());
+        if let Some((counted_at, stats)) = self
+            .bucket_stats
+            .lock()
+            .expect("bucket stats lock poisoned")
+            .get(&entry)
+        {
+            if now
+                .duration_since(*counted_at)
+                .is_ok_and(|age| age < BUCKET_STATS_TTL)
+            {
+                return Ok(*stats);
+            }
+        }
+
+        let machine = Bucket::attach(address)
+            .await
+            .map_err(|e| S3Error::new(S3ErrorCode::Custom(ByteString::from(e.to_string()))))?;
+        let mut stats = BucketStats::default();
+        let mut start_key = None;
+        loop {
+            let page = machine
+                .query(
+                    self.provider.deref(),
+                    QueryOptions {
+                        prefix: prefix.to_string(),
+                        delimiter: String::new(),
+                        start_key: start_key.take(),
+                        limit: crate::s3::MAX_LIST_OBJECTS_KEYS,
+                        ..Default::default()
+                    },
+                )
+                .await
+                .map_err(|e| S3Error::new(S3ErrorCode::Custom(ByteString::from(e.to_string()))))?;
+            for (key, object) in page.objects {
+                if key.starts_with(UPLOAD_PARTS_PREFIX.as_bytes()) {
+                    continue;
+                }
+                stats.objects += 1;
+                stats.bytes += object.size;
+            }
+            match page.next_key {
+                Some(next_key) => start_key = Some(next_key),
+                None => break,
+            }
+        }
+
+        self.bucket_stats
+            .lock()
+            .expect("bucket stats lock poisoned")
+            .insert(entry, (now, stats));
+        Ok(stats)
+    }
+
+    /// Downloads every object in the bucket and checks it against its recorded etag. Objects
+    /// whose etag can't be recomputed from their stored bytes are reported as unverifiable.
+    pub async fn verify_bucket(
+        &self,
+        bucket: &BucketNameWithOwner,
+    ) -> anyhow::Result<Vec<(String, ETagCheck)>> {
+        let address = self
+            .get_bucket_address_by_alias(bucket)
+            .await?
+            .ok_or_else(|| anyhow!("bucket {} not found", bucket.name()))?;
+        let machine = Bucket::attach(address).await?;
+
+        let mut checks = Vec::new();
+        let mut start_key = None;
+        loop {
+            let page = machine
+                .query(
+                    self.provider.deref(),
+                    QueryOptions {
+                        start_key: start_key.take(),
+                        limit: crate::s3::MAX_LIST_OBJECTS_KEYS,
+                        ..Default::default()
+                    },
+                )
+                .await?;
+            for (key, object) in page.objects {
+                // Parts of uploads in progress aren't objects yet.
+                if key.starts_with(UPLOAD_PARTS_PREFIX.as_bytes()) {
+                    continue;
+                }
+                // One object that can't be checked shouldn't cut the report short.
+                let key = match String::from_utf8(key) {
+                    Ok(key) => key,
+                    Err(e) => {
+                        let key = String::from_utf8_lossy(e.as_bytes()).into_owned();
+                        checks.push((key, ETagCheck::Unverifiable("key is not valid UTF-8")));
+                        continue;
+                    }
+                };
+                let check = match self.verify_object(&machine, &key, &object).await {
+                    Ok(check) => check,
+                    Err(e) => {
+                        warn!(key = %key, error = %e, "failed to verify object");
+                        ETagCheck::Unverifiable("download failed")
+                    }
+                };
+                checks.push((key, check));
+            }
+            match page.next_key {
+                Some(next_key) => start_key = Some(next_key),
+                None => break,
+            }
+        }
+        Ok(checks)
+    }
+
+    async fn verify_object(
+        &self,
+        machine: &Bucket,
+        key: &str,
+        object: &ObjectState,
+    ) -> anyhow::Result<ETagCheck> {
+        let Some(e_tag) = object.metadata.get(ETAG_METADATA_KEY) else {
+            return Ok(ETagCheck::Unverifiable("no etag recorded"));
+        };
+        // The etag was computed over the bytes the client sent, not their encoded form.
+        if object.metadata.contains_key(PLAINTEXT_LENGTH_METADATA_KEY) {
+            return Ok(ETagCheck::Unverifiable("stored encoded"));
+        }
+        let part_size = object
+            .metadata
+            .get(PART_SIZE_METADATA_KEY)
+            .and_then(|v| v.parse().ok());
+
+        let (writer, reader) = tokio::io::duplex(VERIFY_BUFFER_SIZE);
+        let (downloaded, check) = tokio::join!(
+            machine.get(
+                self.provider.deref(),
+                key,
+                writer,
+                GetOptions {
+                    range: None,
+                    height: FvmQueryHeight::Committed,
+                    show_progress: false,
+                },
+            ),
+            verify_e_tag(
+                ExactLengthReader::new(reader, object.size),
+                e_tag,
+                part_size
+            ),
+        );
+        downloaded?;
+        Ok(check?)
+    }
+
     pub async fn get_object(
         &self,
         machine: &Bucket,
         key: &ObjectKey,
     ) -> Result<ObjectState, S3Error> {
+        let address = machine.address();
+        if let Some(missing) = &self.missing_keys {
+            if missing.contains(address, key, self.clock.now()) {
+                return Err(s3_error!(NoSuchKey));
+            }
+        }
+
         let object_list = machine
             .query(
                 self.provider.deref(),
@@ -68,31 +577,165 @@ where
             .await
             .map_err(|e| S3Error::new(S3ErrorCode::Custom(ByteString::from(e.to_string()))))?;
 
-        if let Some((_, object_state)) = object_list.objects.into_iter().next() {
-            return Ok(object_state);
+        // The query matches on prefix, so make sure the first result is the key itself.
+        if let Some((object_key, object_state)) = object_list.objects.into_iter().next() {
+            if object_key == key.as_bytes() {
+                return Ok(object_state);
+            }
         }
 
+        if let Some(missing) = &self.missing_keys {
+            missing.insert(address, key, self.clock.now());
+        }
         Err(s3_error!(NoSuchKey))
     }
+
+    /// Forgets any cached miss of a key that was just written.
+    pub fn key_written(&self, address: Address, key: &str) {
+        if let Some(missing) = &self.missing_keys {
+            missing.remove(address, key);
+        }
+    }
+
     pub async fn get_bucket_address_by_alias(
         &self,
         bucket: &BucketNameWithOwner,
     ) -> Result<Option<Address>, S3Error> {
+        Ok(self
+            .get_bucket_metadata(bucket)
+            .await?
+            .map(|(address, _)| address))
+    }
+
+    /// Address and stored metadata (creation date, alias) of a bucket, if it exists.
+    pub async fn get_bucket_metadata(
+        &self,
+        bucket: &BucketNameWithOwner,
+    ) -> Result<Option<(Address, HashMap<String, String>)>, S3Error> {
         let signer = &Void::new(bucket.owner());
         let list = Bucket::list(self.provider.deref(), signer, FvmQueryHeight::Committed)
             .await
             .map_err(|e| S3Error::new(S3ErrorCode::Custom(ByteString::from(e.to_string()))))?;
 
         let alias = bucket.name();
-        for item in list {
-            if let Some(v) = item.metadata.get(crate::s3::ALIAS_METADATA_KEY) {
-                if v.eq(&alias) {
-                    return Ok(Some(item.address));
+        Ok(list.into_iter().find_map(|item| {
+            item.metadata
+                .get(crate::metadata::ALIAS_METADATA_KEY)
+                .is_some_and(|v| *v == alias)
+                .then_some((item.address, item.metadata))
+        }))
+    }
+
+    /// Like [`Self::get_bucket_path`], with the owner given by the caller rather than taken from
+    /// the wallet. It must be a `0x`-prefixed address, and agree with any owner prefix in the
+    /// bucket name.
+    pub fn get_bucket_path_with_owner(
+        &self,
+        bucket: &BucketName,
+        owner: &str,
+    ) -> Result<BucketNameWithOwner, S3Error> {
+        let is_address = owner.len() == 42
+            && owner.starts_with("0x")
+            && ethers::types::Address::from_str(owner).is_ok();
+        if !is_address {
+            return Err(s3_error!(
+                InvalidArgument,
+                "owner must be a 0x-prefixed address, got {owner}"
+            ));
+        }
+
+        check_owner_prefix(bucket)?;
+        match split_eth_address(bucket) {
+            Some((addr, bucket_name)) => {
+                let bucket = BucketNameWithOwner::from(&addr, &bucket_name)?;
+                if BucketNameWithOwner::from(owner, &bucket_name)?.owner() != bucket.owner() {
+                    return Err(s3_error!(
+                        InvalidRequest,
+                        "owner doesn't match the bucket name's owner prefix"
+                    ));
                 }
+                Ok(bucket)
             }
+            None => BucketNameWithOwner::from(owner, bucket),
         }
+    }
 
-        Ok(None)
+    /// Like [`Self::get_bucket_path`], for requests that change the bucket. Writes are signed by
+    /// the wallet, which can only write to its own buckets; the chain would refuse the write
+    /// anyway, but with an opaque error.
+    pub fn write_bucket_path(&self, bucket: &BucketName) -> Result<BucketNameWithOwner, S3Error> {
+        let path = self.get_bucket_path(bucket)?;
+        if let Some(wallet) = &self.wallet {
+            if path.owner() != wallet.address() {
+                return Err(s3_error!(
+                    AccessDenied,
+                    "bucket {bucket} isn't owned by the gateway's wallet {}, so it can't be written",
+                    wallet
+                        .eth_address()
+                        .expect("wallet must have eth address")
+                        .encode_hex_with_prefix()
+                ));
+            }
+        }
+        Ok(path)
+    }
+
+    /// Bucket a create request names. Buckets are always created for the wallet, so an owner
+    /// prefix in the name must be the wallet's address.
+    pub fn new_bucket_path(&self, bucket: &BucketName) -> Result<BucketNameWithOwner, S3Error> {
+        let Some(wallet) = &self.wallet else {
+            return Err(s3_error!(
+                AccessDenied,
+                "buckets can only be created by a gateway with a wallet"
+            ));
+        };
+        let eth_address = wallet
+            .eth_address()
+            .expect("wallet must have eth address")
+            .encode_hex_with_prefix();
+
+        check_owner_prefix(bucket)?;
+        match split_eth_address(bucket) {
+            Some((addr, _)) if !eth_address.eq_ignore_ascii_case(&addr) => Err(s3_error!(
+                InvalidBucketName,
+                "buckets can only be created for the wallet's address {eth_address}"
+            )),
+            Some((addr, bucket_name)) => BucketNameWithOwner::from(&addr, &bucket_name),
+            None => BucketNameWithOwner::from(&eth_address, bucket),
+        }
+    }
+
+    /// Creates a bucket owned by the wallet and returns its address. Used by the `CreateBucket`
+    /// handler and the `create-bucket` subcommand alike.
+    pub async fn create_bucket(&self, bucket: &BucketNameWithOwner) -> Result<Address, S3Error> {
+        let Some(mut wallet) = self.wallet.clone() else {
+            return Err(s3_error!(
+                AccessDenied,
+                "buckets can only be created by a gateway with a wallet"
+            ));
+        };
+
+        if self.get_bucket_address_by_alias(bucket).await?.is_some() {
+            return Err(s3_error!(BucketAlreadyExists));
+        }
+
+        let creation_date = try_!(self.clock.now().duration_since(UNIX_EPOCH)).as_secs();
+        let (machine, _) = Bucket::new(
+            self.provider.deref(),
+            &mut wallet,
+            None,
+            HashMap::from([
+                (
+                    CREATION_DATE_METADATA_KEY.to_string(),
+                    creation_date.to_string(),
+                ),
+                (ALIAS_METADATA_KEY.to_string(), bucket.name()),
+            ]),
+            GasParams::default(),
+        )
+        .await
+        .map_err(|e| S3Error::new(S3ErrorCode::Custom(ByteString::from(e.to_string()))))?;
+        Ok(machine.address())
     }
 
     /// Given a bucket name figure out the full bucket path with the bucket owner address prefix according to the following rules:
@@ -103,6 +746,7 @@ where
             .wallet
             .as_ref()
             .map(|wallet| wallet.eth_address().expect("wallet must have eth address"));
+        check_owner_prefix(bucket)?;
         match split_eth_address(bucket) {
             Some((addr, bucket_name)) => BucketNameWithOwner::from(&addr, &bucket_name),
             None => {
@@ -113,26 +757,81 @@ where
                     );
                 }
 
-                Err(S3Error::new(S3ErrorCode::Custom(ByteString::from(
-                    "owner address prefix is missing".to_string(),
-                ))))
+                Err(invalid_bucket_path())
             }
         }
     }
 }
 
+/// Number of the next part of a complete request, which lists parts in order from 1.
+fn next_part_number(part: &CompletedPart, done: usize) -> Result<PartNumber, S3Error> {
+    let part_number = part
+        .part_number
+        .ok_or_else(|| s3_error!(InvalidRequest, "missing part number"))?;
+    if usize::try_from(part_number).ok() != Some(done + 1) {
+        return Err(s3_error!(InvalidRequest, "invalid part order"));
+    }
+    Ok(part_number)
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::Recall;
-    use bytestring::ByteString;
+    use crate::clock::MockClock;
+    use crate::recall::{BucketStats, UPLOAD_PARTS_PREFIX};
+    use crate::upload::{UploadManifest, UploadPart};
+    use crate::{Recall, RecallConfig};
+    use recall_provider::fvm_shared::address::Address;
     use recall_provider::fvm_shared::chainid::ChainID;
     use recall_provider::json_rpc::{JsonRpcProvider, Url};
     use recall_provider::util::ethers_address_to_fil_address;
+    use recall_provider::Client;
+    use recall_sdk::machine::bucket::Bucket;
+    use recall_sdk::machine::Machine;
     use recall_signer::key::parse_secret_key;
     use recall_signer::{AccountKind, SubnetID, Wallet};
+    use s3s::auth::Credentials;
+    use s3s::dto::CompletedPart;
     use s3s::S3ErrorCode;
+    use std::path::PathBuf;
     use std::str::FromStr;
+    use std::time::{Duration, UNIX_EPOCH};
     use tempfile::tempdir;
+    use uuid::Uuid;
+
+    /// A gateway rooted at `root` whose provider has no node behind it, so only what doesn't
+    /// reach the backend can succeed. With `wallet`, it signs as
+    /// 0xc05fe6b63ffa4b3c518e6ff1e597358ee839db01; without, it's read-only.
+    fn test_recall(root: PathBuf, wallet: bool) -> Recall<impl Client + Send + Sync, Wallet> {
+        test_recall_with_config(root, wallet, RecallConfig::default())
+    }
+
+    fn test_recall_with_config(
+        root: PathBuf,
+        wallet: bool,
+        config: RecallConfig,
+    ) -> Recall<impl Client + Send + Sync, Wallet> {
+        let provider = JsonRpcProvider::new_http(
+            Url::from_str("http://127.0.0.1").unwrap(),
+            ChainID::from(1),
+            None,
+            None,
+        )
+        .expect("json rpc provider should not fail");
+
+        let wallet = wallet.then(|| {
+            let sk = parse_secret_key(
+                "1c323d494d1d069fe4c891350a1ec691c4216c17418a0cb3c7533b143bd2b812",
+            )
+            .expect("parse private key should not fail");
+            Wallet::new_secp256k1(
+                sk,
+                AccountKind::Ethereum,
+                SubnetID::from_str("test").unwrap(),
+            )
+            .unwrap()
+        });
+        Recall::with_config(root, provider, wallet, config).unwrap()
+    }
 
     #[test]
     fn test_get_bucket_path_with_wallet() {
@@ -173,6 +872,373 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_new_bucket_path() {
+        let tmp_dir = tempdir().unwrap();
+
+        let recall = test_recall(tmp_dir.into_path(), true);
+
+        let addr =
+            ethers::types::Address::from_str("0xc05fe6b63ffa4b3c518e6ff1e597358ee839db01").unwrap();
+        let owner = ethers_address_to_fil_address(&addr).unwrap();
+
+        // buckets are created for the wallet, named with or without its address
+        for bucket in ["foo", "0xC05FE6B63FFA4B3C518E6FF1E597358EE839DB01.foo"] {
+            let bucket = recall.new_bucket_path(&bucket.to_string()).unwrap();
+            assert_eq!(bucket.owner(), owner);
+            assert_eq!(bucket.name(), "foo");
+        }
+
+        // but never for another address
+        let err = recall
+            .new_bucket_path(&"0xe1209fb9aa2d08c8541297ec06ee6bbb63b10edc.foo".to_string())
+            .unwrap_err();
+        assert_eq!(*err.code(), S3ErrorCode::InvalidBucketName);
+
+        let err = recall
+            .new_bucket_path(&"0xc05fe6b63ffa4b3c518e6ff1e597358ee839db01".to_string())
+            .unwrap_err();
+        assert_eq!(*err.code(), S3ErrorCode::InvalidBucketName);
+
+        let tmp_dir = tempdir().unwrap();
+        let read_only = test_recall(tmp_dir.into_path(), false);
+        let err = read_only.new_bucket_path(&"foo".to_string()).unwrap_err();
+        assert_eq!(*err.code(), S3ErrorCode::AccessDenied);
+    }
+
+    #[test]
+    fn test_write_bucket_path() {
+        let tmp_dir = tempdir().unwrap();
+
+        let recall = test_recall(tmp_dir.into_path(), true);
+
+        // the wallet's own buckets can be written
+        for bucket in ["foo", "0xc05fe6b63ffa4b3c518e6ff1e597358ee839db01.foo"] {
+            assert!(recall.write_bucket_path(&bucket.to_string()).is_ok());
+        }
+
+        // another address's bucket can be read but not written
+        let bucket = "0xe1209fb9aa2d08c8541297ec06ee6bbb63b10edc.foo".to_string();
+        assert!(recall.get_bucket_path(&bucket).is_ok());
+        let err = recall.write_bucket_path(&bucket).unwrap_err();
+        assert_eq!(*err.code(), S3ErrorCode::AccessDenied);
+        assert!(err
+            .message()
+            .unwrap()
+            .contains("0xc05fe6b63ffa4b3c518e6ff1e597358ee839db01"));
+    }
+
+    #[test]
+    fn test_upload_part_path_uses_temp_dir() {
+        let root = tempdir().unwrap();
+        let scratch = tempdir().unwrap();
+
+        let mut recall = test_recall(root.path().to_path_buf(), false);
+        assert_eq!(recall.temp_dir, root.path());
+
+        recall.temp_dir = scratch.path().to_path_buf();
+        let upload_id = Uuid::new_v4();
+        assert!(recall
+            .get_upload_part_path(&upload_id, 1)
+            .starts_with(scratch.path()));
+        assert!(recall.get_upload_path(&upload_id).starts_with(root.path()));
+    }
+
+    #[test]
+    fn test_with_config() {
+        let root = tempdir().unwrap();
+        let scratch = tempdir().unwrap();
+
+        let config = RecallConfig {
+            temp_dir: Some(scratch.path().to_path_buf()),
+            region: Some("us-west-2".to_string()),
+            max_keys_hard_limit: 2000,
+            ..Default::default()
+        };
+        let recall = test_recall_with_config(root.path().to_path_buf(), false, config);
+        assert_eq!(recall.root, root.path());
+        assert_eq!(recall.temp_dir, scratch.path());
+        assert_eq!(recall.region.as_deref(), Some("us-west-2"));
+        assert_eq!(recall.max_keys_hard_limit, 2000);
+        assert!(!recall.disable_multipart);
+        assert!(recall.is_read_only);
+    }
+
+    #[test]
+    fn test_backend_key_with_key_prefix() {
+        let tmp_dir = tempdir().unwrap();
+
+        let mut recall = test_recall(tmp_dir.into_path(), false);
+        recall
+            .key_prefixes
+            .insert("tenant".to_string(), "tenants/a/".to_string());
+
+        let bucket = recall
+            .get_bucket_path(&"0xc05fe6b63ffa4b3c518e6ff1e597358ee839db01.foo".to_string())
+            .unwrap();
+        let credentials = |access_key: &str| Credentials {
+            access_key: access_key.to_string(),
+            secret_key: "secret".to_string().into(),
+        };
+
+        assert_eq!(recall.backend_key(None, &bucket, "a.txt"), "a.txt");
+        assert_eq!(
+            recall.backend_key(Some(&credentials("admin")), &bucket, "a.txt"),
+            "a.txt"
+        );
+        assert_eq!(
+            recall.backend_key(Some(&credentials("tenant")), &bucket, "a.txt"),
+            "tenants/a/a.txt"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_assemble_parts_keeps_parts() {
+        let root = tempdir().unwrap();
+
+        let recall = test_recall(root.path().to_path_buf(), false);
+
+        let upload_id = Uuid::new_v4();
+        for (part_number, data) in [(1, "hello "), (2, "world")] {
+            std::fs::write(recall.get_upload_part_path(&upload_id, part_number), data).unwrap();
+        }
+        let parts = || {
+            (1..=2).map(|n| CompletedPart {
+                part_number: Some(n),
+                ..Default::default()
+            })
+        };
+
+        let mut first = Vec::new();
+        let (e_tag, part_paths, part_sizes) = recall
+            .assemble_parts(&upload_id, parts(), &mut first)
+            .await
+            .unwrap();
+        assert_eq!(first, b"hello world");
+        assert!(e_tag.ends_with("-2\""));
+        assert_eq!(part_sizes, [6, 5]);
+
+        // Adding the object failed; the parts are still there for a retry.
+        assert!(part_paths.iter().all(|p| p.exists()));
+        let mut retry = Vec::new();
+        let (retry_e_tag, _, _) = recall
+            .assemble_parts(&upload_id, parts(), &mut retry)
+            .await
+            .unwrap();
+        assert_eq!(retry, first);
+        assert_eq!(retry_e_tag, e_tag);
+    }
+
+    #[tokio::test]
+    async fn test_bucket_stats_are_reused() {
+        let root = tempdir().unwrap();
+
+        let mut recall = test_recall(root.path().to_path_buf(), false);
+        let clock = MockClock::new(UNIX_EPOCH);
+        recall.clock = Box::new(clock.clone());
+
+        let address = Address::new_id(1);
+        let stats = BucketStats {
+            objects: 3,
+            bytes: 42,
+        };
+        recall
+            .bucket_stats
+            .lock()
+            .unwrap()
+            .insert((address, String::new()), (UNIX_EPOCH, stats));
+
+        // There's no backend to ask, so only the earlier count can answer.
+        clock.advance(Duration::from_secs(29));
+        assert_eq!(recall.bucket_stats(address, "").await.unwrap(), stats);
+        // A tenant's prefix is counted on its own.
+        assert!(recall.bucket_stats(address, "tenant/").await.is_err());
+
+        // Once it's stale, the bucket is listed again.
+        clock.advance(Duration::from_secs(1));
+        assert!(recall.bucket_stats(address, "").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_missing_key_is_not_queried_again() {
+        let root = tempdir().unwrap();
+
+        let config = RecallConfig {
+            missing_key_ttl: Some(Duration::from_secs(2)),
+            ..RecallConfig::default()
+        };
+        let mut recall = test_recall_with_config(root.path().to_path_buf(), false, config);
+        let clock = MockClock::new(UNIX_EPOCH);
+        recall.clock = Box::new(clock.clone());
+
+        let machine = Bucket::attach(Address::new_id(1)).await.unwrap();
+        let key = "missing.txt".to_string();
+        recall
+            .missing_keys
+            .as_ref()
+            .unwrap()
+            .insert(machine.address(), &key, UNIX_EPOCH);
+
+        // There's no backend to ask, so only the cached miss can answer.
+        let err = recall.get_object(&machine, &key).await.unwrap_err();
+        assert_eq!(*err.code(), S3ErrorCode::NoSuchKey);
+
+        // Once the miss expires, the backend is asked again.
+        clock.advance(Duration::from_secs(2));
+        let err = recall.get_object(&machine, &key).await.unwrap_err();
+        assert_ne!(*err.code(), S3ErrorCode::NoSuchKey);
+    }
+
+    #[tokio::test]
+    async fn test_shared_missing_keys() {
+        let config = || RecallConfig {
+            missing_key_ttl: Some(Duration::from_secs(2)),
+            ..RecallConfig::default()
+        };
+        let first = test_recall_with_config(tempdir().unwrap().into_path(), false, config());
+        let mut second = test_recall_with_config(tempdir().unwrap().into_path(), false, config());
+        second.share_writes_with(&first);
+
+        // A miss one instance records is seen by the other...
+        let machine = Bucket::attach(Address::new_id(1)).await.unwrap();
+        let key = "missing.txt".to_string();
+        first
+            .missing_keys
+            .as_ref()
+            .unwrap()
+            .insert(machine.address(), &key, first.clock.now());
+        let err = second.get_object(&machine, &key).await.unwrap_err();
+        assert_eq!(*err.code(), S3ErrorCode::NoSuchKey);
+
+        // ...and a write through the other clears it, so the backend is asked again.
+        second.key_written(machine.address(), &key);
+        let err = first.get_object(&machine, &key).await.unwrap_err();
+        assert_ne!(*err.code(), S3ErrorCode::NoSuchKey);
+    }
+
+    #[tokio::test]
+    async fn test_backend_parts_match_assembled_parts() {
+        let root = tempdir().unwrap();
+
+        let recall = test_recall(root.path().to_path_buf(), false);
+        let bucket = recall
+            .get_bucket_path(&"0xc05fe6b63ffa4b3c518e6ff1e597358ee839db01.foo".to_string())
+            .unwrap();
+
+        // The same parts, received once as files and once as backend objects.
+        let upload_id = Uuid::new_v4();
+        let mut manifest = UploadManifest::new(&bucket, "a.txt");
+        for (part_number, data) in [(1, "hello "), (2, "world")] {
+            std::fs::write(recall.get_upload_part_path(&upload_id, part_number), data).unwrap();
+            let mut hasher = recall.etag_algorithm.hasher();
+            hasher.update(data);
+            manifest.parts.insert(
+                part_number,
+                UploadPart {
+                    e_tag: hasher.e_tag(),
+                    size: data.len() as u64,
+                },
+            );
+        }
+        let parts = |n| {
+            (1..=n).map(|n| CompletedPart {
+                part_number: Some(n),
+                ..Default::default()
+            })
+        };
+
+        let (file_e_tag, _, file_sizes) = recall
+            .assemble_parts(&upload_id, parts(2), &mut Vec::new())
+            .await
+            .unwrap();
+        let (e_tag, part_keys) = recall
+            .backend_parts(&upload_id, &manifest, parts(2))
+            .unwrap();
+        assert_eq!(e_tag, file_e_tag);
+        assert_eq!(
+            part_keys.iter().map(|(_, size)| *size).collect::<Vec<_>>(),
+            file_sizes
+        );
+        assert!(part_keys
+            .iter()
+            .all(|(key, _)| key.starts_with(UPLOAD_PARTS_PREFIX)));
+
+        let err = recall
+            .backend_parts(&upload_id, &manifest, parts(3))
+            .unwrap_err();
+        assert_eq!(*err.code(), S3ErrorCode::InvalidPart);
+    }
+
+    #[test]
+    fn test_get_bucket_path_with_owner() {
+        let tmp_dir = tempdir().unwrap();
+
+        let recall = test_recall(tmp_dir.into_path(), false);
+
+        let owner = "0xc05fe6b63ffa4b3c518e6ff1e597358ee839db01";
+        let expected =
+            ethers_address_to_fil_address(&ethers::types::Address::from_str(owner).unwrap())
+                .unwrap();
+
+        // Owner names the bucket without a prefix, or confirms the prefix.
+        let bucket = recall
+            .get_bucket_path_with_owner(&"foo".to_string(), owner)
+            .unwrap();
+        assert_eq!(bucket.owner(), expected);
+        assert_eq!(bucket.name(), "foo");
+        let bucket = recall
+            .get_bucket_path_with_owner(&format!("{owner}.foo"), owner)
+            .unwrap();
+        assert_eq!(bucket.owner(), expected);
+
+        let err = recall
+            .get_bucket_path_with_owner(
+                &format!("{owner}.foo"),
+                "0xe1209fb9aa2d08c8541297ec06ee6bbb63b10edc",
+            )
+            .unwrap_err();
+        assert_eq!(*err.code(), S3ErrorCode::InvalidRequest);
+
+        for malformed in [
+            "c05fe6b63ffa4b3c518e6ff1e597358ee839db01",
+            "0x1234",
+            "f410abc",
+        ] {
+            let err = recall
+                .get_bucket_path_with_owner(&"foo".to_string(), malformed)
+                .unwrap_err();
+            assert_eq!(*err.code(), S3ErrorCode::InvalidArgument);
+        }
+    }
+
+    #[test]
+    fn test_part_limits() {
+        let tmp_dir = tempdir().unwrap();
+
+        let mut recall = test_recall(tmp_dir.into_path(), false);
+
+        // Unchecked by default.
+        assert!(recall.check_part_sizes(&[1, 1, 1]).is_ok());
+        assert!(recall.check_part_number(10_001).is_ok());
+
+        recall.min_part_size = Some(5);
+        recall.max_parts = Some(3);
+        assert!(recall.check_part_sizes(&[5, 5, 1]).is_ok());
+        assert!(recall.check_part_sizes(&[1]).is_ok());
+        assert!(recall.check_part_sizes(&[]).is_ok());
+
+        let err = recall.check_part_sizes(&[5, 4, 5]).unwrap_err();
+        assert_eq!(*err.code(), S3ErrorCode::EntityTooSmall);
+        assert!(err.message().unwrap().contains("part 2"));
+
+        let err = recall.check_part_sizes(&[5, 5, 5, 5]).unwrap_err();
+        assert_eq!(*err.code(), S3ErrorCode::InvalidRequest);
+
+        assert!(recall.check_part_number(3).is_ok());
+        let err = recall.check_part_number(4).unwrap_err();
+        assert_eq!(*err.code(), S3ErrorCode::InvalidRequest);
+    }
+
     #[test]
     fn test_get_bucket_path_no_wallet() {
         let tmp_dir = tempdir().unwrap();
@@ -188,14 +1254,19 @@ mod tests {
         let wallet: Option<Wallet> = None;
         let recall = Recall::new(tmp_dir.into_path(), provider, wallet).unwrap();
 
-        // without prefix, throws an error
-        let result = recall.get_bucket_path(&"foo".to_string());
-        assert_eq!(
-            *result.unwrap_err().code(),
-            S3ErrorCode::Custom(ByteString::from(
-                "owner address prefix is missing".to_string(),
-            ))
-        );
+        // without prefix, or without a name after it, throws an error
+        for bucket in [
+            "foo",
+            "0xc05fe6b63ffa4b3c518e6ff1e597358ee839db01",
+            "0xc05fe6b63ffa4b3c518e6ff1e597358ee839db01.",
+        ] {
+            let err = recall.get_bucket_path(&bucket.to_string()).unwrap_err();
+            assert_eq!(*err.code(), S3ErrorCode::InvalidBucketName);
+            assert_eq!(
+                err.message(),
+                Some("bucket must be formatted as <owner-address>.<name>")
+            );
+        }
 
         // with prefix
 
@@ -1,35 +1,56 @@
 use std::collections::HashMap;
+use std::future::Future;
 use std::ops::{Deref, Not};
-use std::time::{SystemTime, UNIX_EPOCH};
-
-use crate::bucket::{split_eth_address, BucketNameWithOwner};
+use std::path::PathBuf;
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::bucket::BucketNameWithOwner;
+use crate::compress::{decompress_reader, StorageCompression};
+use crate::etag::uniform_part_size;
+use crate::key::check_object_key;
+use crate::metadata::{
+    active_retention, compression, content_type, is_reserved, legal_hold, logical_size,
+    storage_class, user_metadata, ALIAS_METADATA_KEY, COMPRESSION_METADATA_KEY,
+    CONTENT_TYPE_METADATA_KEY, CREATION_DATE_METADATA_KEY, ETAG_METADATA_KEY,
+    LAST_MODIFIED_METADATA_KEY, LEGAL_HOLD_METADATA_KEY, LOCK_METADATA_KEYS,
+    PART_SIZE_METADATA_KEY, PLAINTEXT_LENGTH_METADATA_KEY, QUOTA_OWNER_METADATA_KEY,
+    RETAIN_UNTIL_METADATA_KEY, RETENTION_MODE_METADATA_KEY, STORAGE_CLASS_METADATA_KEY,
+};
+use crate::quota::QuotaReservation;
+use crate::recall::{CreateClaim, UPLOAD_PARTS_PREFIX};
+use crate::transform::{apply_transforms, BoxReader, TransformContext};
+use crate::upload::{UploadManifest, UploadPart};
 use crate::utils::hex;
-use crate::utils::{copy_bytes, HashReader};
+use crate::utils::{copy_bytes, AbortOnDrop, Concat, ExactLengthReader};
 use crate::Recall;
 
 use async_tempfile::TempFile;
+use bytes::Bytes;
 use bytestring::ByteString;
-use ethers::utils::hex::ToHexExt;
+use futures::Stream;
 use futures::StreamExt;
 use futures::TryStreamExt;
+use http::{header::CONTENT_RANGE, HeaderValue, StatusCode};
 use lazy_static::lazy_static;
-use md5::Digest;
-use md5::Md5;
 use prometheus::{register_int_counter_vec, IntCounterVec};
+use recall_provider::fvm_shared::address::Address;
+use recall_provider::query::FvmQueryHeight;
 use recall_provider::Client;
-use recall_provider::{message::GasParams, query::FvmQueryHeight};
 use recall_sdk::machine::bucket::AddOptions;
 use recall_sdk::machine::bucket::Bucket;
 use recall_sdk::machine::bucket::DeleteOptions;
 use recall_sdk::machine::bucket::GetOptions;
+use recall_sdk::machine::bucket::ObjectState;
 use recall_sdk::machine::bucket::QueryOptions;
 use recall_sdk::machine::Machine;
 use recall_signer::Signer;
+use s3s::auth::Credentials;
 use s3s::dto::*;
 use s3s::s3_error;
 use s3s::S3Error;
 use s3s::S3ErrorCode;
 use s3s::S3Result;
+use s3s::StdError;
 use s3s::S3;
 use s3s::{S3Request, S3Response};
 use tokio::fs;
@@ -37,34 +58,71 @@ use tokio::io::AsyncSeekExt;
 use tokio::io::AsyncWriteExt;
 use tokio_util::io::ReaderStream;
 use tracing::debug;
+use tracing::info;
 use tracing::log::error;
+use tracing::warn;
 use uuid::Uuid;
 
-static LAST_MODIFIED_METADATA_KEY: &str = "last_modified";
-static CREATION_DATE_METADATA_KEY: &str = "creation_date";
-static ETAG_METADATA_KEY: &str = "etag";
-pub static ALIAS_METADATA_KEY: &str = "alias";
-
-static MAX_LIST_OBJECTS_KEYS: u64 = 1000;
+static FORCE_DELETE_HEADER: &str = "x-recall-force";
+static IF_NONE_MATCH_HEADER: &str = "if-none-match";
+static IF_MATCH_HEADER: &str = "if-match";
+static BYPASS_GOVERNANCE_HEADER: &str = "x-amz-bypass-governance-retention";
+static CONTINUATION_TOKEN_HEADER: &str = "x-recall-continuation-token";
+static TX_HASH_HEADER: &str = "x-recall-tx-hash";
+static DRY_RUN_HEADER: &str = "x-recall-dry-run";
+static OWNER_HEADER: &str = "x-recall-owner";
+static STATS_HEADER: &str = "x-recall-stats";
+static OBJECT_COUNT_HEADER: &str = "x-recall-object-count";
+static TOTAL_SIZE_HEADER: &str = "x-recall-total-size";
+static COMPRESS_HEADER: &str = "x-recall-compress";
+static OWNER_TAG_KEY: &str = "owner";
+/// Version id of every object, as S3 reports it for objects in unversioned buckets. The backend
+/// keeps one version of each key, so clients that record version ids and send them back always
+/// get the same one.
+static NULL_VERSION_ID: &str = "null";
+
+static DEFAULT_REGION: &str = "us-east-1";
+static METRICS_ALL_BUCKETS_LABEL: &str = "all";
+
+/// S3's page size ceiling, and the default `max_keys_hard_limit`.
+pub(crate) static MAX_LIST_OBJECTS_KEYS: u64 = 1000;
+static MAX_LIST_BUCKETS: usize = 10000;
+static MAX_FORCE_DELETE_PAGES: usize = 100;
+/// Bytes in flight between the download and the upload of a copy.
+static COPY_BUFFER_SIZE: usize = 1024 * 1024;
 
 lazy_static! {
     static ref COUNTER_S3_ACTIONS: IntCounterVec = register_int_counter_vec!(
         "recall_s3_call",
         "Number of S3 calls.",
-        &["action", "status"]
+        &["action", "status", "bucket"]
     )
     .unwrap();
+    static ref COUNTER_S3_UNIMPLEMENTED: IntCounterVec = register_int_counter_vec!(
+        "recall_s3_unimplemented_total",
+        "Number of calls to unsupported S3 operations.",
+        &["action"]
+    )
+    .unwrap();
+}
+
+/// Stored bytes of an object and the access key whose quota they're charged to.
+struct QuotaCharge {
+    access_key: String,
+    bytes: u64,
 }
 
 struct S3ActionCounter {
     action: &'static str,
+    bucket: String,
     success: bool,
 }
 
 impl S3ActionCounter {
-    fn new(action: &'static str) -> Self {
+    fn new(action: &'static str, bucket: Option<&str>) -> Self {
         Self {
             action,
+            bucket: bucket.unwrap_or(METRICS_ALL_BUCKETS_LABEL).to_string(),
             success: false,
         }
     }
@@ -74,11 +132,568 @@ impl Drop for S3ActionCounter {
     fn drop(&mut self) {
         let status = if self.success { "success" } else { "error" };
         COUNTER_S3_ACTIONS
-            .with_label_values(&[self.action, status])
+            .with_label_values(&[self.action, status, self.bucket.as_str()])
             .inc();
     }
 }
 
+impl<C, S> Recall<C, S>
+where
+    C: Client + Send + Sync,
+    S: Signer,
+{
+    /// Buckets are only used as a metric label when enabled, to keep cardinality bounded.
+    fn action_counter(&self, action: &'static str, bucket: &str) -> S3ActionCounter {
+        S3ActionCounter::new(action, self.metrics_per_bucket.then_some(bucket))
+    }
+
+    /// Reserves quota for `bytes` about to be stored with the request's credentials, over the
+    /// `replaced` bytes of an object they overwrite if those are charged to the same key.
+    /// Requests without credentials, or servers without quotas, aren't limited.
+    fn reserve_quota(
+        &self,
+        credentials: Option<&Credentials>,
+        bytes: u64,
+        replaced: Option<&QuotaCharge>,
+    ) -> S3Result<Option<QuotaReservation<'_>>> {
+        let (Some(quota), Some(credentials)) = (self.quota.as_deref(), credentials) else {
+            return Ok(None);
+        };
+        let replaced = replaced
+            .filter(|replaced| replaced.access_key == credentials.access_key)
+            .map_or(0, |replaced| replaced.bytes);
+        let growth = bytes.saturating_sub(replaced);
+        QuotaReservation::new(quota, &credentials.access_key, growth)
+            .map(Some)
+            .ok_or_else(quota_exceeded)
+    }
+
+    /// Rejects a write that can't fit the credentials' quota, before its body is received.
+    fn check_quota(&self, credentials: Option<&Credentials>, bytes: u64) -> S3Result<()> {
+        match (self.quota.as_deref(), credentials) {
+            (Some(quota), Some(credentials)) if !quota.allows(&credentials.access_key, bytes) => {
+                Err(quota_exceeded())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Quota charge of the object a write or delete is about to remove, so it can be given
+    /// back. Only looked up when quotas are enforced.
+    async fn replaced_charge(&self, machine: &Bucket, key: &str) -> S3Result<Option<QuotaCharge>> {
+        if self.quota.is_none() {
+            return Ok(None);
+        }
+        match self.get_object(machine, &key.to_string()).await {
+            Ok(object) => Ok(quota_charge(object.size, &object.metadata)),
+            Err(e) if *e.code() == S3ErrorCode::NoSuchKey => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Gives an object's charge back to the access key that stored it.
+    fn release_quota(&self, charge: Option<QuotaCharge>) {
+        if let (Some(quota), Some(charge)) = (self.quota.as_deref(), charge) {
+            quota.release(&charge.access_key, charge.bytes);
+        }
+    }
+
+    /// Gives back the charge of an object overwritten by `bytes` stored with the request's
+    /// credentials. Their reservation was already netted against a charge of their own.
+    fn release_replaced(
+        &self,
+        credentials: Option<&Credentials>,
+        replaced: Option<QuotaCharge>,
+        bytes: u64,
+    ) {
+        let replaced = replaced.map(|mut replaced| {
+            if credentials.is_some_and(|c| c.access_key == replaced.access_key) {
+                replaced.bytes = replaced.bytes.saturating_sub(bytes);
+            }
+            replaced
+        });
+        self.release_quota(replaced);
+    }
+
+    /// Streams an object's stored bytes from `src_machine` into `dst_key`, stored with
+    /// `metadata`, and returns the hash of the write. The download is piped straight into the
+    /// upload, so this takes a small buffer rather than scratch space for the whole object. A
+    /// download that breaks off fails the upload instead of storing a truncated object.
+    async fn stream_object(
+        &self,
+        src_machine: Bucket,
+        src_key: String,
+        dst_machine: &Bucket,
+        dst_key: &str,
+        size: u64,
+        metadata: HashMap<String, String>,
+    ) -> S3Result<String> {
+        let mut wallet = match &self.wallet {
+            Some(w) => w.to_owned(),
+            None => unreachable!(),
+        };
+
+        let (writer, reader) = tokio::io::duplex(COPY_BUFFER_SIZE);
+        let provider = self.provider.clone();
+        let timeout = self.object_api_timeout;
+        let download = tokio::spawn(async move {
+            let _ = object_api_call(
+                timeout,
+                src_machine.get(
+                    provider.deref(),
+                    src_key.as_str(),
+                    writer,
+                    GetOptions {
+                        range: None,
+                        height: FvmQueryHeight::Committed,
+                        show_progress: false,
+                    },
+                ),
+            )
+            .await
+            .map_err(|err| error!("failed to download object: {}", err));
+        });
+
+        let added = object_api_call(
+            self.object_api_timeout,
+            dst_machine.add_reader(
+                self.provider.deref(),
+                &mut wallet,
+                dst_key,
+                ExactLengthReader::new(reader, size),
+                size,
+                AddOptions {
+                    metadata,
+                    ..AddOptions::default()
+                },
+            ),
+        )
+        .await;
+        download.abort();
+        Ok(added?.hash().to_string())
+    }
+
+    /// Refuses a storage class other than the gateway's, which it couldn't honour.
+    fn check_storage_class(&self, requested: Option<&StorageClass>) -> S3Result<()> {
+        match requested {
+            Some(class) if class.as_str() != self.storage_class => Err(s3_error!(
+                InvalidStorageClass,
+                "objects can only be stored with storage class {}",
+                self.storage_class
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks a write's precondition against the object currently at `backend_key`, right
+    /// before the write. Create-only writes get a claim on `backend_key`, to hold until the write
+    /// lands so concurrent create-only writes through this gateway can't both pass the check.
+    /// Claiming the backend key keeps tenants writing the same client key apart.
+    async fn check_write_precondition(
+        &self,
+        precondition: Option<&WritePrecondition>,
+        address: Address,
+        machine: &Bucket,
+        backend_key: &str,
+    ) -> S3Result<Option<CreateClaim<'_>>> {
+        match precondition {
+            None => Ok(None),
+            Some(WritePrecondition::Absent) => {
+                let claim = self
+                    .claim_create(address, backend_key)
+                    .ok_or_else(|| s3_error!(PreconditionFailed))?;
+                match self.get_object(machine, &backend_key.to_string()).await {
+                    Ok(_) => Err(s3_error!(PreconditionFailed)),
+                    Err(e) if *e.code() == S3ErrorCode::NoSuchKey => Ok(Some(claim)),
+                    Err(e) => Err(e),
+                }
+            }
+            Some(WritePrecondition::Matches(expected)) => {
+                let object = self.get_object(machine, &backend_key.to_string()).await?;
+                let e_tag = object.metadata.get(ETAG_METADATA_KEY);
+                let matches = expected == "*"
+                    || e_tag
+                        .is_some_and(|e_tag| e_tag.trim_matches('"') == expected.trim_matches('"'));
+                if !matches {
+                    return Err(s3_error!(PreconditionFailed));
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    /// Bucket of a read request. Authenticated clients may name the bucket's owner with the
+    /// `x-recall-owner` header, so a gateway acting for its users can serve reads of any owner's
+    /// buckets. Writes ignore the header and always act for the gateway's wallet.
+    fn read_bucket_path(
+        &self,
+        headers: &http::HeaderMap,
+        credentials: Option<&Credentials>,
+        bucket: &BucketName,
+    ) -> S3Result<BucketNameWithOwner> {
+        let Some(owner) = headers.get(OWNER_HEADER) else {
+            return self.get_bucket_path(bucket);
+        };
+        if credentials.is_none() {
+            return Err(s3_error!(
+                AccessDenied,
+                "{OWNER_HEADER} is only accepted on authenticated requests"
+            ));
+        }
+        let owner = owner
+            .to_str()
+            .map_err(|_| s3_error!(InvalidArgument, "{OWNER_HEADER} isn't an address"))?;
+        self.get_bucket_path_with_owner(bucket, owner)
+    }
+
+    /// Stores a multipart part streamed from the client as the hidden object `part_key`, without
+    /// buffering it on local disk.
+    async fn store_part<S>(
+        &self,
+        machine: &Bucket,
+        part_key: &str,
+        stream: S,
+        size: u64,
+    ) -> S3Result<()>
+    where
+        S: Stream<Item = Result<Bytes, StdError>> + Send + Unpin,
+    {
+        let mut wallet = match &self.wallet {
+            Some(w) => w.to_owned(),
+            None => unreachable!(),
+        };
+
+        let (mut writer, reader) = tokio::io::duplex(COPY_BUFFER_SIZE);
+        let (received, added) = tokio::join!(
+            // Dropping the writer once the body is copied ends the upload's input.
+            async move { copy_bytes(stream, &mut writer).await },
+            object_api_call(
+                self.object_api_timeout,
+                machine.add_reader(
+                    self.provider.deref(),
+                    &mut wallet,
+                    part_key,
+                    ExactLengthReader::new(reader, size),
+                    size,
+                    AddOptions::default(),
+                ),
+            ),
+        );
+        added?;
+        if received? != size {
+            return Err(s3_error!(
+                IncompleteBody,
+                "the part doesn't match its content length"
+            ));
+        }
+        Ok(())
+    }
+
+    /// Streams the concatenation of `parts`, hidden objects of the bucket at `address` given
+    /// with their sizes, into `dst_key` and returns the hash of the write. The parts are
+    /// downloaded one after the other, each through a small buffer.
+    async fn concat_parts(
+        &self,
+        address: Address,
+        parts: &[(String, u64)],
+        dst_key: &str,
+        metadata: HashMap<String, String>,
+    ) -> S3Result<String> {
+        let mut wallet = match &self.wallet {
+            Some(w) => w.to_owned(),
+            None => unreachable!(),
+        };
+        let src_machine = Bucket::attach(address)
+            .await
+            .map_err(|e| S3Error::new(S3ErrorCode::Custom(ByteString::from(e.to_string()))))?;
+        let machine = Bucket::attach(address)
+            .await
+            .map_err(|e| S3Error::new(S3ErrorCode::Custom(ByteString::from(e.to_string()))))?;
+
+        let mut downloads = Vec::new();
+        let mut readers = Vec::new();
+        for (key, size) in parts {
+            let (writer, reader) = tokio::io::duplex(COPY_BUFFER_SIZE);
+            downloads.push((key.clone(), writer));
+            readers.push(ExactLengthReader::new(reader, *size));
+        }
+        let size = parts.iter().map(|(_, size)| size).sum();
+
+        let provider = self.provider.clone();
+        let timeout = self.object_api_timeout;
+        // A part that fails to download drops the writers left, which fails the upload.
+        let download = tokio::spawn(async move {
+            for (key, writer) in downloads {
+                if let Err(err) = object_api_call(
+                    timeout,
+                    src_machine.get(
+                        provider.deref(),
+                        key.as_str(),
+                        writer,
+                        GetOptions {
+                            range: None,
+                            height: FvmQueryHeight::Committed,
+                            show_progress: false,
+                        },
+                    ),
+                )
+                .await
+                {
+                    error!("failed to download part {}: {}", key, err);
+                    return;
+                }
+            }
+        });
+
+        let added = object_api_call(
+            self.object_api_timeout,
+            machine.add_reader(
+                self.provider.deref(),
+                &mut wallet,
+                dst_key,
+                Concat::new(readers),
+                size,
+                AddOptions {
+                    metadata,
+                    ..AddOptions::default()
+                },
+            ),
+        )
+        .await;
+        download.abort();
+        Ok(added?.hash().to_string())
+    }
+
+    /// Deletes multipart parts stored in the backend, once their upload is completed or aborted.
+    async fn delete_parts(&self, machine: &Bucket, part_keys: &[String]) -> S3Result<()> {
+        let mut wallet = match &self.wallet {
+            Some(w) => w.to_owned(),
+            None => unreachable!(),
+        };
+        for part_key in part_keys {
+            let tx = machine
+                .delete(
+                    self.provider.deref(),
+                    &mut wallet,
+                    part_key,
+                    DeleteOptions::default(),
+                )
+                .await
+                .map_err(|e| S3Error::new(S3ErrorCode::Custom(ByteString::from(e.to_string()))))?;
+            debug!(hash = ?tx.hash(), status = ?tx.status, "deleted part");
+        }
+        Ok(())
+    }
+
+    /// Looks up an object whose lock is read or changed, with its bucket's address and its
+    /// backend key.
+    async fn lock_target(
+        &self,
+        credentials: Option<&Credentials>,
+        bucket: &BucketNameWithOwner,
+        key: &str,
+    ) -> S3Result<(Address, String, ObjectState)> {
+        let Some(address) = self.get_bucket_address_by_alias(bucket).await? else {
+            return Err(s3_error!(NoSuchBucket));
+        };
+        let machine = Bucket::attach(address)
+            .await
+            .map_err(|e| S3Error::new(S3ErrorCode::Custom(ByteString::from(e.to_string()))))?;
+
+        let key = self.backend_key(credentials, bucket, key);
+        let object = self.get_object(&machine, &key).await?;
+        Ok((address, key, object))
+    }
+
+    /// Stores an object again with its lock changed. The backend can't update metadata in
+    /// place, so the object is rewritten onto itself.
+    async fn rewrite_lock(
+        &self,
+        address: Address,
+        key: String,
+        object: ObjectState,
+        metadata: HashMap<String, String>,
+    ) -> S3Result<String> {
+        let src_machine = Bucket::attach(address)
+            .await
+            .map_err(|e| S3Error::new(S3ErrorCode::Custom(ByteString::from(e.to_string()))))?;
+        let machine = Bucket::attach(address)
+            .await
+            .map_err(|e| S3Error::new(S3ErrorCode::Custom(ByteString::from(e.to_string()))))?;
+        self.stream_object(
+            src_machine,
+            key.clone(),
+            &machine,
+            &key,
+            object.size,
+            metadata,
+        )
+        .await
+    }
+
+    /// Refuses to delete or overwrite an object under a legal hold or unexpired retention.
+    /// Governance retention gives way when the request asks to bypass it. Without write-once storage in
+    /// the backend this is best-effort: the lock only holds for requests through the gateway.
+    async fn check_unlocked(
+        &self,
+        machine: &Bucket,
+        key: &str,
+        bypass_governance: bool,
+    ) -> S3Result<()> {
+        let object = match self.get_object(machine, &key.to_string()).await {
+            Ok(object) => object,
+            Err(e) if *e.code() == S3ErrorCode::NoSuchKey => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        self.check_metadata_unlocked(&object.metadata, bypass_governance)
+    }
+
+    /// [`Self::check_unlocked`] for an object whose metadata is already at hand.
+    fn check_metadata_unlocked(
+        &self,
+        metadata: &HashMap<String, String>,
+        bypass_governance: bool,
+    ) -> S3Result<()> {
+        if legal_hold(metadata) {
+            return Err(s3_error!(AccessDenied, "the object is under a legal hold"));
+        }
+        let now = try_!(self.clock.now().duration_since(UNIX_EPOCH)).as_secs();
+        match active_retention(metadata, now) {
+            Some((mode, _))
+                if !(bypass_governance && mode == ObjectLockRetentionMode::GOVERNANCE) =>
+            {
+                Err(s3_error!(
+                    AccessDenied,
+                    "the object is under {mode} retention"
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Answers a probe of a bucket setting the gateway doesn't support with what S3 reports for
+    /// a bucket that never configured it. Tools probing every setting, like Terraform, treat
+    /// `NotImplemented` as fatal. The call is still counted as unimplemented.
+    async fn not_configured<T>(
+        &self,
+        action: &'static str,
+        bucket: &str,
+        output: S3Result<T>,
+    ) -> S3Result<S3Response<T>> {
+        let mut action_counter = self.action_counter(action, bucket);
+        COUNTER_S3_UNIMPLEMENTED.with_label_values(&[action]).inc();
+
+        let bucket = self.get_bucket_path(bucket)?;
+        let Some(_) = self.get_bucket_address_by_alias(&bucket).await? else {
+            return Err(s3_error!(NoSuchBucket));
+        };
+
+        let output = output?;
+        action_counter.success = true;
+        Ok(S3Response::new(output))
+    }
+}
+
+/// Rejects a write on a server without a wallet. It's reported as denied rather than
+/// unsupported so clients don't retry it or fall back to another operation.
+fn read_only<T>(operation: &str) -> S3Result<T> {
+    Err(s3_error!(
+        AccessDenied,
+        "{operation} is not permitted, the server is read-only"
+    ))
+}
+
+/// Charge of an object stored with `size` bytes, if its writer was charged for it.
+fn quota_charge(size: u64, metadata: &HashMap<String, String>) -> Option<QuotaCharge> {
+    metadata
+        .get(QUOTA_OWNER_METADATA_KEY)
+        .map(|access_key| QuotaCharge {
+            access_key: access_key.clone(),
+            bytes: size,
+        })
+}
+
+fn quota_exceeded() -> S3Error {
+    let mut err = S3Error::with_message(
+        S3ErrorCode::Custom(ByteString::from_static("QuotaExceeded")),
+        "storing this would exceed the credentials' quota",
+    );
+    err.set_status_code(StatusCode::FORBIDDEN);
+    err
+}
+
+/// Calls the object API, which moves blob bytes and can take much longer than the RPC calls
+/// around it. Calls that run out of time are reported as the backend being unavailable.
+async fn object_api_call<T, E: std::fmt::Display>(
+    timeout: Option<Duration>,
+    call: impl Future<Output = Result<T, E>>,
+) -> S3Result<T> {
+    let result = match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, call).await.map_err(|_| {
+            s3_error!(
+                ServiceUnavailable,
+                "the object API didn't finish within {timeout:?}"
+            )
+        })?,
+        None => call.await,
+    };
+    result.map_err(|e| S3Error::new(S3ErrorCode::Custom(ByteString::from(e.to_string()))))
+}
+
+/// Object lock fields of GetObject and HeadObject: retention mode and end, and legal hold.
+fn object_lock_fields(
+    metadata: &HashMap<String, String>,
+) -> (
+    Option<ObjectLockMode>,
+    Option<Timestamp>,
+    Option<ObjectLockLegalHoldStatus>,
+) {
+    let mode = metadata
+        .get(RETENTION_MODE_METADATA_KEY)
+        .map(|v| ObjectLockMode::from(v.clone()));
+    let retain_until = metadata
+        .get(RETAIN_UNTIL_METADATA_KEY)
+        .and_then(|v| Timestamp::parse(TimestampFormat::EpochSeconds, v.as_str()).ok());
+    let legal_hold = metadata
+        .get(LEGAL_HOLD_METADATA_KEY)
+        .map(|v| ObjectLockLegalHoldStatus::from(v.clone()));
+    (mode, retain_until, legal_hold)
+}
+
+/// Checks a retention mode and end date to be stored, returning the end as epoch seconds.
+fn parse_retention(mode: &str, retain_until: Timestamp, now: u64) -> S3Result<u64> {
+    if mode != ObjectLockRetentionMode::GOVERNANCE && mode != ObjectLockRetentionMode::COMPLIANCE {
+        return Err(s3_error!(InvalidArgument, "unknown retention mode {mode}"));
+    }
+    let retain_until = u64::try_from(time::OffsetDateTime::from(retain_until).unix_timestamp())
+        .map_err(|_| s3_error!(InvalidArgument, "the retain until date is before 1970"))?;
+    if retain_until <= now {
+        return Err(s3_error!(
+            InvalidArgument,
+            "the retain until date must be in the future"
+        ));
+    }
+    Ok(retain_until)
+}
+
+/// Where the parts of a multipart upload were received, and so where completing it reads them.
+enum UploadedParts {
+    /// Assembled into `file` from the part files at `part_paths`.
+    Files {
+        file: TempFile,
+        part_paths: Vec<PathBuf>,
+    },
+    /// Hidden objects in the upload's bucket, by key with their sizes.
+    Objects(Vec<(String, u64)>),
+}
+
+/// Rejects an operation the gateway doesn't support, counting the call to show which missing
+/// operations clients actually use.
+fn not_implemented<T>(action: &'static str, operation: &str) -> S3Result<T> {
+    COUNTER_S3_UNIMPLEMENTED.with_label_values(&[action]).inc();
+    Err(s3_error!(NotImplemented, "{operation} is not implemented"))
+}
+
 #[async_trait::async_trait]
 impl<C, S> S3 for Recall<C, S>
 where
@@ -90,19 +705,59 @@ where
         &self,
         req: S3Request<AbortMultipartUploadInput>,
     ) -> S3Result<S3Response<AbortMultipartUploadOutput>> {
-        let mut action_counter = S3ActionCounter::new("abort_multipart_upload");
+        let mut action_counter = self.action_counter("abort_multipart_upload", &req.input.bucket);
         if self.is_read_only {
-            return Err(s3_error!(
-                NotImplemented,
-                "AbortMultipartUpload is not implemented in read-only mode"
-            ));
+            return read_only("AbortMultipartUpload");
         }
 
-        let AbortMultipartUploadInput { upload_id, .. } = req.input;
+        let AbortMultipartUploadInput {
+            bucket,
+            key,
+            upload_id,
+            ..
+        } = req.input;
 
+        let bucket = self.write_bucket_path(&bucket)?;
         let upload_id = Uuid::parse_str(&upload_id).map_err(|_| s3_error!(InvalidRequest))?;
-        let prefix = format!(".upload_id-{upload_id}");
-        let mut iter = try_!(fs::read_dir(&self.root).await);
+
+        // Drop the manifest first so no part can be recorded while the parts are removed.
+        let manifest = {
+            let _lock = self.upload_manifests.lock().await;
+            let upload_path = self.get_upload_path(&upload_id);
+            let manifest = UploadManifest::load(&upload_path).await?;
+            if let Some(manifest) = &manifest {
+                // Parts in the backend are deleted from the bucket named in the request.
+                if self.multipart_parts_in_backend && !manifest.matches(&bucket, &key) {
+                    return Err(s3_error!(
+                        InvalidRequest,
+                        "bucket and key don't match the multipart upload"
+                    ));
+                }
+                try_!(fs::remove_file(&upload_path).await);
+            }
+            manifest
+        };
+
+        // Parts stored in the backend are only known from the manifest.
+        if let Some(manifest) = manifest.filter(|m| !m.parts.is_empty()) {
+            if self.multipart_parts_in_backend {
+                let Some(address) = self.get_bucket_address_by_alias(&bucket).await? else {
+                    return Err(s3_error!(NoSuchBucket));
+                };
+                let machine = Bucket::attach(address).await.map_err(|e| {
+                    S3Error::new(S3ErrorCode::Custom(ByteString::from(e.to_string())))
+                })?;
+                let part_keys: Vec<String> = manifest
+                    .parts
+                    .keys()
+                    .map(|n| self.get_upload_part_key(&upload_id, *n))
+                    .collect();
+                self.delete_parts(&machine, &part_keys).await?;
+            }
+        }
+
+        let prefix = format!(".upload-{upload_id}.");
+        let mut iter = try_!(fs::read_dir(&self.temp_dir).await);
         while let Some(entry) = try_!(iter.next_entry().await) {
             let file_type = try_!(entry.file_type().await);
             if file_type.is_file().not() {
@@ -118,6 +773,7 @@ where
                 try_!(fs::remove_file(entry.path()).await);
             }
         }
+
         action_counter.success = true;
         Ok(S3Response::new(AbortMultipartUploadOutput {
             ..Default::default()
@@ -129,14 +785,17 @@ where
         &self,
         req: S3Request<CompleteMultipartUploadInput>,
     ) -> S3Result<S3Response<CompleteMultipartUploadOutput>> {
-        let mut action_counter = S3ActionCounter::new("complete_multipart_upload");
+        let mut action_counter =
+            self.action_counter("complete_multipart_upload", &req.input.bucket);
         if self.is_read_only {
-            return Err(s3_error!(
-                NotImplemented,
-                "CompleteMultipartUpload is not implemented in read-only mode"
-            ));
+            return read_only("CompleteMultipartUpload");
         }
 
+        // A long upload mustn't clobber an object that changed while it was running, so the
+        // precondition is checked against the object as it is at completion.
+        let precondition = write_precondition(&req.headers)?;
+        let bypass_governance = bypass_governance(&req.headers);
+
         let CompleteMultipartUploadInput {
             multipart_upload,
             bucket,
@@ -145,40 +804,39 @@ where
             ..
         } = req.input;
 
-        let bucket = self.get_bucket_path(&bucket)?;
+        let bucket = self.write_bucket_path(&bucket)?;
 
         let Some(multipart_upload) = multipart_upload else {
             return Err(s3_error!(InvalidPart));
         };
 
         let upload_id = Uuid::parse_str(&upload_id).map_err(|_| s3_error!(InvalidRequest))?;
+        let upload_path = self.get_upload_path(&upload_id);
 
-        let mut file = try_!(TempFile::new().await);
-
-        let mut cnt: i32 = 0;
-        let mut e_tag_hash = <Md5 as Digest>::new();
-        for part in multipart_upload.parts.into_iter().flatten() {
-            let part_number = part
-                .part_number
-                .ok_or_else(|| s3_error!(InvalidRequest, "missing part number"))?;
-            cnt += 1;
-            if part_number != cnt {
-                return Err(s3_error!(InvalidRequest, "invalid part order"));
-            }
-
-            let part_path = self.get_upload_part_path(&upload_id, part_number);
-            let reader = try_!(fs::File::open(&part_path).await);
-            let mut hash_reader = HashReader::new(reader);
-            let _ = try_!(tokio::io::copy(&mut hash_reader, &mut file).await);
-            e_tag_hash.update(hash_reader.finalize());
-            try_!(fs::remove_file(&part_path).await);
+        let Some(manifest) = UploadManifest::load(&upload_path).await? else {
+            return Err(s3_error!(NoSuchUpload));
+        };
+        if !manifest.matches(&bucket, &key) {
+            return Err(s3_error!(
+                InvalidRequest,
+                "bucket and key don't match the multipart upload"
+            ));
         }
 
-        try_!(file.flush().await);
-        try_!(file.rewind().await);
-
-        let md5_sum = hex(e_tag_hash.finalize());
-        let e_tag = format!("\"{md5_sum}-{cnt}\"");
+        let parts = multipart_upload.parts.into_iter().flatten();
+        let (e_tag, part_sizes, uploaded) = if self.multipart_parts_in_backend {
+            let (e_tag, part_keys) = self.backend_parts(&upload_id, &manifest, parts)?;
+            let part_sizes = part_keys.iter().map(|(_, size)| *size).collect();
+            (e_tag, part_sizes, UploadedParts::Objects(part_keys))
+        } else {
+            let mut file = try_!(TempFile::new_in(self.temp_dir.as_path()).await);
+            let (e_tag, part_paths, part_sizes) =
+                self.assemble_parts(&upload_id, parts, &mut file).await?;
+            try_!(file.rewind().await);
+            (e_tag, part_sizes, UploadedParts::Files { file, part_paths })
+        };
+        self.check_part_sizes(&part_sizes)?;
+        let size = part_sizes.iter().sum();
 
         let mut wallet = match &self.wallet {
             Some(w) => w.clone(),
@@ -192,35 +850,102 @@ where
             .await
             .map_err(|e| S3Error::new(S3ErrorCode::Custom(ByteString::from(e.to_string()))))?;
 
-        let last_modified = try_!(SystemTime::now().duration_since(UNIX_EPOCH)).as_secs();
-        let _ = machine
-            .add_from_path(
-                self.provider.deref(),
-                &mut wallet,
-                &key,
-                file.file_path(),
-                AddOptions {
-                    metadata: HashMap::from([
-                        (
-                            LAST_MODIFIED_METADATA_KEY.to_string(),
-                            last_modified.to_string(),
-                        ),
-                        (ETAG_METADATA_KEY.to_string(), e_tag.to_string()),
-                    ]),
-                    ..AddOptions::default()
-                },
+        let backend_key = self.backend_key(req.credentials.as_ref(), &bucket, &key);
+        self.check_unlocked(&machine, &backend_key, bypass_governance)
+            .await?;
+        let _create_claim = self
+            .check_write_precondition(precondition.as_ref(), address, &machine, &backend_key)
+            .await?;
+        let replaced = self.replaced_charge(&machine, &backend_key).await?;
+        let reservation = self.reserve_quota(req.credentials.as_ref(), size, replaced.as_ref())?;
+
+        let last_modified = try_!(self.clock.now().duration_since(UNIX_EPOCH)).as_secs();
+        let mut metadata = HashMap::from([
+            (
+                LAST_MODIFIED_METADATA_KEY.to_string(),
+                last_modified.to_string(),
+            ),
+            (ETAG_METADATA_KEY.to_string(), e_tag.to_string()),
+            (
+                STORAGE_CLASS_METADATA_KEY.to_string(),
+                self.storage_class.clone(),
+            ),
+        ]);
+        if let Some(part_size) = uniform_part_size(&part_sizes) {
+            metadata.insert(PART_SIZE_METADATA_KEY.to_string(), part_size.to_string());
+        }
+        if let Some(reservation) = &reservation {
+            metadata.insert(
+                QUOTA_OWNER_METADATA_KEY.to_string(),
+                reservation.access_key().to_string(),
+            );
+        }
+        let tx = match &uploaded {
+            UploadedParts::Files { file, .. } => object_api_call(
+                self.object_api_timeout,
+                machine.add_from_path(
+                    self.provider.deref(),
+                    &mut wallet,
+                    &backend_key,
+                    file.file_path(),
+                    AddOptions {
+                        metadata,
+                        ..AddOptions::default()
+                    },
+                ),
             )
-            .await
-            .map_err(|e| S3Error::new(S3ErrorCode::Custom(ByteString::from(e.to_string()))))?;
+            .await?
+            .hash()
+            .to_string(),
+            UploadedParts::Objects(part_keys) => {
+                self.concat_parts(address, part_keys, &backend_key, metadata)
+                    .await?
+            }
+        };
+        self.key_written(address, &backend_key);
+        if let Some(reservation) = reservation {
+            reservation.commit();
+        }
+        self.release_replaced(req.credentials.as_ref(), replaced, size);
+
+        // Only now that the object is stored can the upload go; until then a retry needs it.
+        // The object is committed either way, so leftovers are only logged, not failed on.
+        let _lock = self.upload_manifests.lock().await;
+        match uploaded {
+            UploadedParts::Files { part_paths, .. } => {
+                for part_path in part_paths {
+                    if let Err(e) = fs::remove_file(&part_path).await {
+                        warn!(
+                            path = %part_path.display(),
+                            error = %e,
+                            "failed to remove upload part"
+                        );
+                    }
+                }
+            }
+            UploadedParts::Objects(part_keys) => {
+                let part_keys: Vec<String> = part_keys.into_iter().map(|(key, _)| key).collect();
+                if let Err(e) = self.delete_parts(&machine, &part_keys).await {
+                    warn!(%upload_id, error = %e, "failed to delete upload parts");
+                }
+            }
+        }
+        if let Err(e) = fs::remove_file(&upload_path).await {
+            warn!(path = %upload_path.display(), error = %e, "failed to remove upload manifest");
+        }
 
         let output = CompleteMultipartUploadOutput {
             e_tag: Some(e_tag),
             bucket: Some(bucket.name()),
             key: Some(key),
+            version_id: Some(NULL_VERSION_ID.to_string()),
             ..Default::default()
         };
+        let mut resp = S3Response::new(output);
+        set_tx_hash_header(&mut resp, tx);
+
         action_counter.success = true;
-        Ok(S3Response::new(output))
+        Ok(resp)
     }
 
     // #[tracing::instrument]
@@ -228,8 +953,14 @@ where
         &self,
         req: S3Request<CopyObjectInput>,
     ) -> S3Result<S3Response<CopyObjectOutput>> {
-        let mut action_counter = S3ActionCounter::new("copy_object");
+        let mut action_counter = self.action_counter("copy_object", &req.input.bucket);
+        if self.is_read_only {
+            return read_only("CopyObject");
+        }
+        let bypass_governance = bypass_governance(&req.headers);
         let input = req.input;
+        check_object_key(&input.key, self.safe_object_keys)?;
+        self.check_storage_class(input.storage_class.as_ref())?;
         let (src_bucket, src_key) = match input.copy_source {
             CopySource::AccessPoint { .. } => return Err(s3_error!(NotImplemented)),
             CopySource::Bucket {
@@ -239,94 +970,140 @@ where
             } => (self.get_bucket_path(&bucket.to_string())?, key.to_string()),
         };
 
-        let (dst_bucket, dst_key) = (self.get_bucket_path(&input.bucket)?, input.key);
+        let (dst_bucket, dst_key) = (self.write_bucket_path(&input.bucket)?, input.key);
+        let src_key = self.backend_key(req.credentials.as_ref(), &src_bucket, &src_key);
+        let dst_key = self.backend_key(req.credentials.as_ref(), &dst_bucket, &dst_key);
+
+        let replace_metadata = input
+            .metadata_directive
+            .as_ref()
+            .is_some_and(|v| v.as_str() == MetadataDirective::REPLACE);
+        let self_copy = src_bucket == dst_bucket && src_key == dst_key;
+        if self_copy && !replace_metadata {
+            return Err(s3_error!(
+                InvalidRequest,
+                "copying an object to itself requires replacing its metadata"
+            ));
+        }
+        if replace_metadata {
+            if let Some(reserved) = input
+                .metadata
+                .iter()
+                .flatten()
+                .map(|(k, _)| k)
+                .find(|k| is_reserved(k))
+            {
+                return Err(s3_error!(
+                    InvalidArgument,
+                    "metadata key {reserved} is reserved"
+                ));
+            }
+        }
 
-        // Download object to a file
         let Some(src_address) = self.get_bucket_address_by_alias(&src_bucket).await? else {
             return Err(s3_error!(NoSuchBucket));
         };
 
-        let machine = Bucket::attach(src_address)
+        // The backend has no server-side copy of an object blob, nor a way to point a key at an
+        // existing one, so the bytes always go through the gateway. Same-bucket copies (e.g.
+        // renames) at least skip the second bucket lookup.
+        let dst_address = if src_bucket == dst_bucket {
+            src_address
+        } else {
+            let Some(dst_address) = self.get_bucket_address_by_alias(&dst_bucket).await? else {
+                return Err(s3_error!(NoSuchBucket));
+            };
+            dst_address
+        };
+
+        let src_machine = Bucket::attach(src_address)
             .await
             .map_err(|e| S3Error::new(S3ErrorCode::Custom(ByteString::from(e.to_string()))))?;
 
-        let src_object = self.get_object(&machine, &src_key).await?;
-
-        let mut file = try_!(TempFile::new().await);
-        let (writer, mut reader) = tokio::io::duplex(4096);
-
-        let provider = self.provider.clone();
-        tokio::spawn(async move {
-            let _ = machine
-                .get(
-                    provider.deref(),
-                    src_key.as_str(),
-                    writer,
-                    GetOptions {
-                        range: None,
-                        height: FvmQueryHeight::Committed,
-                        show_progress: false,
-                    },
-                )
-                .await
-                .map_err(|err| error!("failed to download object: {}", err));
-        });
-
-        try_!(tokio::io::copy(&mut reader, &mut file).await);
-
-        // Upload file
-        try_!(file.flush().await);
-        try_!(file.rewind().await);
-
-        let mut wallet = match &self.wallet {
-            Some(w) => w.to_owned(),
-            None => unreachable!(),
-        };
-
-        let Some(dst_address) = self.get_bucket_address_by_alias(&dst_bucket).await? else {
-            return Err(s3_error!(NoSuchBucket));
-        };
+        let src_object = self.get_object(&src_machine, &src_key).await?;
 
         let machine = Bucket::attach(dst_address)
             .await
             .map_err(|e| S3Error::new(S3ErrorCode::Custom(ByteString::from(e.to_string()))))?;
 
-        let last_modified = try_!(SystemTime::now().duration_since(UNIX_EPOCH)).as_secs();
+        // A self-copy keeps the object's bytes and lock, so only another object's lock is in the
+        // way.
+        if !self_copy {
+            self.check_unlocked(&machine, &dst_key, bypass_governance)
+                .await?;
+        }
 
-        let e_tag = src_object
-            .metadata
-            .get(ETAG_METADATA_KEY)
-            .ok_or(S3Error::new(S3ErrorCode::Custom(ByteString::from(
+        let last_modified = try_!(self.clock.now().duration_since(UNIX_EPOCH)).as_secs();
+
+        let Some(e_tag) = src_object.metadata.get(ETAG_METADATA_KEY).cloned() else {
+            return Err(S3Error::new(S3ErrorCode::Custom(ByteString::from(
                 "no etag".to_string(),
-            ))))?;
+            ))));
+        };
+
+        // The bytes are copied as stored, so the gateway's own metadata describing them (etag,
+        // encoding) carries over and nothing is hashed again. An object lock stays with the
+        // object it was set on. The content type and user metadata are the source's unless
+        // they're replaced. The backend can't update metadata in place, so even a self-copy
+        // that only replaces metadata rewrites the object, keeping its lock.
+        let mut metadata: HashMap<String, String> = src_object
+            .metadata
+            .iter()
+            .filter(|(k, _)| is_reserved(k) && k.as_str() != CONTENT_TYPE_METADATA_KEY)
+            .filter(|(k, _)| self_copy || !LOCK_METADATA_KEYS.contains(&k.as_str()))
+            .filter(|(k, _)| k.as_str() != QUOTA_OWNER_METADATA_KEY)
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        metadata.insert(
+            LAST_MODIFIED_METADATA_KEY.to_string(),
+            last_modified.to_string(),
+        );
+        if replace_metadata {
+            if let Some(content_type) = input.content_type {
+                metadata.insert(
+                    CONTENT_TYPE_METADATA_KEY.to_string(),
+                    content_type.to_string(),
+                );
+            }
+            metadata.extend(input.metadata.into_iter().flatten());
+        } else {
+            metadata.extend(
+                src_object
+                    .metadata
+                    .iter()
+                    .filter(|(k, _)| !is_reserved(k) || k.as_str() == CONTENT_TYPE_METADATA_KEY)
+                    .map(|(k, v)| (k.clone(), v.clone())),
+            );
+        }
 
-        let total_size = file
-            .seek(std::io::SeekFrom::End(0))
-            .await
-            .map_err(|e| S3Error::new(S3ErrorCode::Custom(ByteString::from(e.to_string()))))?;
+        let replaced = self.replaced_charge(&machine, &dst_key).await?;
+        let reservation =
+            self.reserve_quota(req.credentials.as_ref(), src_object.size, replaced.as_ref())?;
+        if let Some(reservation) = &reservation {
+            metadata.insert(
+                QUOTA_OWNER_METADATA_KEY.to_string(),
+                reservation.access_key().to_string(),
+            );
+        }
 
-        let _ = machine
-            .add_reader(
-                self.provider.deref(),
-                &mut wallet,
+        let tx_hash = self
+            .stream_object(
+                src_machine,
+                src_key,
+                &machine,
                 &dst_key,
-                file,
-                total_size,
-                AddOptions {
-                    metadata: HashMap::from([
-                        (
-                            LAST_MODIFIED_METADATA_KEY.to_string(),
-                            last_modified.to_string(),
-                        ),
-                        (ETAG_METADATA_KEY.to_string(), e_tag.to_string()),
-                    ]),
-                    ..AddOptions::default()
-                },
+                src_object.size,
+                metadata,
             )
-            .await
-            .map_err(|e| S3Error::new(S3ErrorCode::Custom(ByteString::from(e.to_string()))))?;
+            .await?;
+        self.key_written(dst_address, &dst_key);
+        if let Some(reservation) = reservation {
+            reservation.commit();
+        }
+        self.release_replaced(req.credentials.as_ref(), replaced, src_object.size);
 
         let copy_object_result = CopyObjectResult {
+            e_tag: Some(e_tag),
             last_modified: Timestamp::parse(
                 TimestampFormat::EpochSeconds,
                 last_modified.to_string().as_str(),
@@ -337,11 +1114,14 @@ where
 
         let output = CopyObjectOutput {
             copy_object_result: Some(copy_object_result),
+            version_id: Some(NULL_VERSION_ID.to_string()),
             ..Default::default()
         };
+        let mut resp = S3Response::new(output);
+        set_tx_hash_header(&mut resp, tx_hash);
 
         action_counter.success = true;
-        Ok(S3Response::new(output))
+        Ok(resp)
     }
 
     // #[tracing::instrument]
@@ -349,56 +1129,30 @@ where
         &self,
         req: S3Request<CreateBucketInput>,
     ) -> S3Result<S3Response<CreateBucketOutput>> {
-        let mut action_counter = S3ActionCounter::new("create_bucket");
+        let mut action_counter = self.action_counter("create_bucket", &req.input.bucket);
         if self.is_read_only {
-            return Err(s3_error!(
-                NotImplemented,
-                "CreateBucket is not implemented in read-only mode"
-            ));
+            return read_only("CreateBucket");
         }
 
-        let mut wallet = match &self.wallet {
-            Some(w) => w.clone(),
-            None => unreachable!(),
-        };
-
-        let eth_address = wallet.eth_address().expect("eth address must exist");
-        let bucket = if let Some((addr, bucket_name)) = split_eth_address(&req.input.bucket) {
-            if !eth_address
-                .encode_hex_with_prefix()
-                .eq_ignore_ascii_case(&addr)
-            {
-                return Err(s3_error!(InvalidBucketName));
+        // An empty constraint means us-east-1, which S3 accepts everywhere.
+        let location = req
+            .input
+            .create_bucket_configuration
+            .as_ref()
+            .and_then(|cfg| cfg.location_constraint.as_ref())
+            .map(BucketLocationConstraint::as_str)
+            .filter(|location| !location.is_empty() && *location != DEFAULT_REGION);
+        if let (Some(location), Some(region)) = (location, &self.region) {
+            if location != region.as_str() {
+                return Err(s3_error!(
+                    InvalidLocationConstraint,
+                    "the {location} location constraint is incompatible with the {region} region"
+                ));
             }
-
-            BucketNameWithOwner::from(&addr, &bucket_name)?
-        } else {
-            BucketNameWithOwner::from(&eth_address.encode_hex_with_prefix(), &req.input.bucket)?
-        };
-
-        if self.get_bucket_address_by_alias(&bucket).await?.is_some() {
-            return Err(s3_error!(BucketAlreadyExists));
         }
 
-        let creation_date = try_!(SystemTime::now().duration_since(UNIX_EPOCH)).as_secs();
-
-        let (machine, _) = Bucket::new(
-            self.provider.deref(),
-            &mut wallet,
-            None,
-            HashMap::from([
-                (
-                    CREATION_DATE_METADATA_KEY.to_string(),
-                    creation_date.to_string(),
-                ),
-                (ALIAS_METADATA_KEY.to_string(), bucket.name()),
-            ]),
-            GasParams::default(),
-        )
-        .await
-        .map_err(|e| S3Error::new(S3ErrorCode::Custom(ByteString::from(e.to_string()))))?;
-
-        let address = machine.address().to_string();
+        let bucket = self.new_bucket_path(&req.input.bucket)?;
+        let address = self.create_bucket(&bucket).await?.to_string();
 
         action_counter.success = true;
         Ok(S3Response::new(CreateBucketOutput {
@@ -411,17 +1165,34 @@ where
         &self,
         req: S3Request<CreateMultipartUploadInput>,
     ) -> S3Result<S3Response<CreateMultipartUploadOutput>> {
-        let mut action_counter = S3ActionCounter::new("create_multipart_upload");
+        let mut action_counter = self.action_counter("create_multipart_upload", &req.input.bucket);
+        if self.disable_multipart {
+            return Err(s3_error!(
+                NotImplemented,
+                "multipart uploads are disabled, upload the object with PutObject"
+            ));
+        }
         if self.is_read_only {
+            return read_only("CreateMultipartUpload");
+        }
+        // Parts are stored as they're uploaded, so there's no single body to compress.
+        if storage_compression(&req.headers)?.is_some() {
             return Err(s3_error!(
                 NotImplemented,
-                "CreateMultipartUpload is not implemented in read-only mode"
+                "{COMPRESS_HEADER} is only supported by PutObject"
             ));
         }
 
         let input = req.input;
+        check_object_key(&input.key, self.safe_object_keys)?;
+        self.check_storage_class(input.storage_class.as_ref())?;
+        let bucket = self.write_bucket_path(&input.bucket)?;
         let upload_id = Uuid::new_v4();
 
+        UploadManifest::new(&bucket, &input.key)
+            .save(&self.get_upload_path(&upload_id))
+            .await?;
+
         let output = CreateMultipartUploadOutput {
             bucket: Some(input.bucket),
             key: Some(input.key),
@@ -433,20 +1204,172 @@ where
         Ok(S3Response::new(output))
     }
 
+    // #[tracing::instrument]
+    async fn delete_bucket(
+        &self,
+        req: S3Request<DeleteBucketInput>,
+    ) -> S3Result<S3Response<DeleteBucketOutput>> {
+        let mut action_counter = self.action_counter("delete_bucket", &req.input.bucket);
+        if self.is_read_only {
+            return read_only("DeleteBucket");
+        }
+        if !self.key_prefix(req.credentials.as_ref()).is_empty() {
+            return Err(s3_error!(
+                AccessDenied,
+                "buckets shared between tenants can't be deleted by a tenant"
+            ));
+        }
+
+        let force = req
+            .headers
+            .get(FORCE_DELETE_HEADER)
+            .is_some_and(|v| v.as_bytes().eq_ignore_ascii_case(b"true"));
+
+        let bucket = self.write_bucket_path(&req.input.bucket)?;
+        let Some(address) = self.get_bucket_address_by_alias(&bucket).await? else {
+            return Err(s3_error!(NoSuchBucket));
+        };
+
+        let machine = Bucket::attach(address)
+            .await
+            .map_err(|e| S3Error::new(S3ErrorCode::Custom(ByteString::from(e.to_string()))))?;
+
+        let mut wallet = match &self.wallet {
+            Some(w) => w.clone(),
+            None => unreachable!(),
+        };
+
+        // Walk the bucket in listing-sized pages, collecting the keys to delete when forced. The
+        // number of pages is bounded so a huge bucket can't pin the request forever. Nothing is
+        // deleted until the whole bucket was checked, so a locked object refuses the request
+        // without emptying the bucket around it.
+        let bypass_governance = bypass_governance(&req.headers);
+        let mut keys = Vec::new();
+        let mut start_key = None;
+        for _ in 0..MAX_FORCE_DELETE_PAGES {
+            let response = machine
+                .query(
+                    self.provider.deref(),
+                    QueryOptions {
+                        delimiter: String::new(),
+                        start_key: start_key.take(),
+                        limit: MAX_LIST_OBJECTS_KEYS,
+                        ..Default::default()
+                    },
+                )
+                .await
+                .map_err(|e| S3Error::new(S3ErrorCode::Custom(ByteString::from(e.to_string()))))?;
+
+            if response.objects.is_empty() {
+                break;
+            }
+            if !force {
+                return Err(s3_error!(BucketNotEmpty));
+            }
+
+            for (key, object) in response.objects {
+                let key = try_!(String::from_utf8(key));
+                self.check_metadata_unlocked(&object.metadata, bypass_governance)
+                    .map_err(|e| {
+                        s3_error!(
+                            AccessDenied,
+                            "{key} can't be deleted: {}",
+                            e.message().unwrap_or_default()
+                        )
+                    })?;
+                keys.push((key, quota_charge(object.size, &object.metadata)));
+            }
+
+            start_key = response.next_key;
+            if start_key.is_none() {
+                break;
+            }
+        }
+
+        if start_key.is_some() {
+            return Err(s3_error!(
+                BucketNotEmpty,
+                "bucket has too many objects to delete in a single request"
+            ));
+        }
+
+        let deleted = keys.len();
+        for (key, charge) in keys {
+            let tx = machine
+                .delete(
+                    self.provider.deref(),
+                    &mut wallet,
+                    key.as_str(),
+                    DeleteOptions::default(),
+                )
+                .await
+                .map_err(|e| S3Error::new(S3ErrorCode::Custom(ByteString::from(e.to_string()))))?;
+
+            debug!(hash = ?tx.hash(), status = ?tx.status);
+            self.release_quota(charge);
+        }
+
+        if force {
+            info!(bucket = %bucket.name(), deleted, "deleted bucket objects");
+        }
+
+        // Bucket machines can't be removed from the chain, so the best we can do is leave the
+        // bucket empty.
+        action_counter.success = true;
+        Ok(S3Response::new(DeleteBucketOutput::default()))
+    }
+
+    // #[tracing::instrument]
+    async fn delete_bucket_cors(
+        &self,
+        _req: S3Request<DeleteBucketCorsInput>,
+    ) -> S3Result<S3Response<DeleteBucketCorsOutput>> {
+        not_implemented("delete_bucket_cors", "DeleteBucketCors")
+    }
+
+    // #[tracing::instrument]
+    async fn delete_bucket_lifecycle(
+        &self,
+        _req: S3Request<DeleteBucketLifecycleInput>,
+    ) -> S3Result<S3Response<DeleteBucketLifecycleOutput>> {
+        not_implemented("delete_bucket_lifecycle", "DeleteBucketLifecycle")
+    }
+
+    // #[tracing::instrument]
+    async fn delete_bucket_policy(
+        &self,
+        _req: S3Request<DeleteBucketPolicyInput>,
+    ) -> S3Result<S3Response<DeleteBucketPolicyOutput>> {
+        not_implemented("delete_bucket_policy", "DeleteBucketPolicy")
+    }
+
+    // #[tracing::instrument]
+    async fn delete_bucket_replication(
+        &self,
+        _req: S3Request<DeleteBucketReplicationInput>,
+    ) -> S3Result<S3Response<DeleteBucketReplicationOutput>> {
+        not_implemented("delete_bucket_replication", "DeleteBucketReplication")
+    }
+
+    // #[tracing::instrument]
+    async fn delete_bucket_tagging(
+        &self,
+        _req: S3Request<DeleteBucketTaggingInput>,
+    ) -> S3Result<S3Response<DeleteBucketTaggingOutput>> {
+        not_implemented("delete_bucket_tagging", "DeleteBucketTagging")
+    }
+
     // #[tracing::instrument]
     async fn delete_object(
         &self,
         req: S3Request<DeleteObjectInput>,
     ) -> S3Result<S3Response<DeleteObjectOutput>> {
-        let mut action_counter = S3ActionCounter::new("delete_object");
+        let mut action_counter = self.action_counter("delete_object", &req.input.bucket);
         if self.is_read_only {
-            return Err(s3_error!(
-                NotImplemented,
-                "DeleteObject is not implemented in read-only mode"
-            ));
+            return read_only("DeleteObject");
         }
 
-        let bucket = self.get_bucket_path(&req.input.bucket)?;
+        let bucket = self.write_bucket_path(&req.input.bucket)?;
         let Some(address) = self.get_bucket_address_by_alias(&bucket).await? else {
             return Err(s3_error!(NoSuchBucket));
         };
@@ -460,7 +1383,14 @@ where
             None => unreachable!(),
         };
 
-        let key = req.input.key;
+        let key = self.backend_key(req.credentials.as_ref(), &bucket, &req.input.key);
+        self.check_unlocked(
+            &machine,
+            &key,
+            req.input.bypass_governance_retention.unwrap_or_default(),
+        )
+        .await?;
+        let deleted = self.replaced_charge(&machine, &key).await?;
         let tx = machine
             .delete(
                 self.provider.deref(),
@@ -472,10 +1402,25 @@ where
             .map_err(|e| S3Error::new(S3ErrorCode::Custom(ByteString::from(e.to_string()))))?;
 
         debug!(hash = ?tx.hash(), status = ?tx.status);
+        self.release_quota(deleted);
+
+        let output = DeleteObjectOutput {
+            version_id: Some(NULL_VERSION_ID.to_string()),
+            ..Default::default()
+        };
+        let mut resp = S3Response::new(output);
+        set_tx_hash_header(&mut resp, tx.hash());
 
         action_counter.success = true;
-        let output = DeleteObjectOutput::default(); // TODO: handle other fields
-        Ok(S3Response::new(output))
+        Ok(resp)
+    }
+
+    // #[tracing::instrument]
+    async fn delete_object_tagging(
+        &self,
+        _req: S3Request<DeleteObjectTaggingInput>,
+    ) -> S3Result<S3Response<DeleteObjectTaggingOutput>> {
+        not_implemented("delete_object_tagging", "DeleteObjectTagging")
     }
 
     // #[tracing::instrument]
@@ -483,15 +1428,16 @@ where
         &self,
         req: S3Request<DeleteObjectsInput>,
     ) -> S3Result<S3Response<DeleteObjectsOutput>> {
-        let mut action_counter = S3ActionCounter::new("delete_objects");
-        if self.is_read_only {
-            return Err(s3_error!(
-                NotImplemented,
-                "DeleteObjects is not implemented in read-only mode"
-            ));
+        let mut action_counter = self.action_counter("delete_objects", &req.input.bucket);
+        let dry_run = req
+            .headers
+            .get(DRY_RUN_HEADER)
+            .is_some_and(|v| v.as_bytes().eq_ignore_ascii_case(b"true"));
+        if self.is_read_only && !dry_run {
+            return read_only("DeleteObjects");
         }
 
-        let bucket = self.get_bucket_path(&req.input.bucket)?;
+        let bucket = self.write_bucket_path(&req.input.bucket)?;
         let Some(address) = self.get_bucket_address_by_alias(&bucket).await? else {
             return Err(s3_error!(NoSuchBucket));
         };
@@ -499,22 +1445,64 @@ where
             .await
             .map_err(|e| S3Error::new(S3ErrorCode::Custom(ByteString::from(e.to_string()))))?;
 
+        // Report which keys would be deleted and which are missing, without deleting anything.
+        if dry_run {
+            let mut deleted = Vec::new();
+            let mut errors = Vec::new();
+            for object in req.input.delete.objects {
+                match self
+                    .get_object(
+                        &machine,
+                        &self.backend_key(req.credentials.as_ref(), &bucket, &object.key),
+                    )
+                    .await
+                {
+                    Ok(_) => deleted.push(DeletedObject {
+                        key: Some(object.key),
+                        ..Default::default()
+                    }),
+                    Err(e) if *e.code() == S3ErrorCode::NoSuchKey => {
+                        errors.push(s3s::dto::Error {
+                            code: Some(S3ErrorCode::NoSuchKey.as_str().to_string()),
+                            key: Some(object.key),
+                            message: Some("The specified key does not exist.".to_string()),
+                            ..Default::default()
+                        });
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+
+            action_counter.success = true;
+            return Ok(S3Response::new(DeleteObjectsOutput {
+                deleted: Some(deleted),
+                errors: Some(errors),
+                ..Default::default()
+            }));
+        }
+
         let mut wallet = match &self.wallet {
             Some(w) => w.clone(),
             None => unreachable!(),
         };
+        let bypass_governance = req.input.bypass_governance_retention.unwrap_or_default();
         for object in req.input.delete.objects {
+            let key = self.backend_key(req.credentials.as_ref(), &bucket, &object.key);
+            self.check_unlocked(&machine, &key, bypass_governance)
+                .await?;
+            let deleted = self.replaced_charge(&machine, &key).await?;
             let tx = machine
                 .delete(
                     self.provider.deref(),
                     &mut wallet,
-                    object.key.as_str(),
+                    &key,
                     DeleteOptions::default(),
                 )
                 .await
                 .map_err(|e| S3Error::new(S3ErrorCode::Custom(ByteString::from(e.to_string()))))?;
 
             debug!(hash = ?tx.hash(), status = ?tx.status);
+            self.release_quota(deleted);
         }
 
         action_counter.success = true;
@@ -522,25 +1510,162 @@ where
         Ok(S3Response::new(output))
     }
 
-    //#[tracing::instrument]
+    // #[tracing::instrument]
+    async fn get_bucket_accelerate_configuration(
+        &self,
+        req: S3Request<GetBucketAccelerateConfigurationInput>,
+    ) -> S3Result<S3Response<GetBucketAccelerateConfigurationOutput>> {
+        self.not_configured(
+            "get_bucket_accelerate_configuration",
+            &req.input.bucket,
+            Ok(GetBucketAccelerateConfigurationOutput::default()),
+        )
+        .await
+    }
+
+    // #[tracing::instrument]
+    async fn get_bucket_cors(
+        &self,
+        _req: S3Request<GetBucketCorsInput>,
+    ) -> S3Result<S3Response<GetBucketCorsOutput>> {
+        not_implemented("get_bucket_cors", "GetBucketCors")
+    }
+
+    // #[tracing::instrument]
+    async fn get_bucket_lifecycle_configuration(
+        &self,
+        _req: S3Request<GetBucketLifecycleConfigurationInput>,
+    ) -> S3Result<S3Response<GetBucketLifecycleConfigurationOutput>> {
+        not_implemented(
+            "get_bucket_lifecycle_configuration",
+            "GetBucketLifecycleConfiguration",
+        )
+    }
+
+    // #[tracing::instrument]
     async fn get_bucket_location(
         &self,
-        _req: S3Request<GetBucketLocationInput>,
+        req: S3Request<GetBucketLocationInput>,
     ) -> S3Result<S3Response<GetBucketLocationOutput>> {
-        let mut action_counter = S3ActionCounter::new("get_bucket_location");
-        let output = GetBucketLocationOutput::default();
+        let mut action_counter = self.action_counter("get_bucket_location", &req.input.bucket);
+        let bucket =
+            self.read_bucket_path(&req.headers, req.credentials.as_ref(), &req.input.bucket)?;
+        let Some(_) = self.get_bucket_address_by_alias(&bucket).await? else {
+            return Err(s3_error!(NoSuchBucket));
+        };
+
+        // Buckets in the default region have no location constraint.
+        let location_constraint = self
+            .region
+            .as_deref()
+            .filter(|region| *region != DEFAULT_REGION)
+            .map(|region| BucketLocationConstraint::from(region.to_string()));
+        let output = GetBucketLocationOutput {
+            location_constraint,
+        };
         action_counter.success = true;
         Ok(S3Response::new(output))
     }
 
+    // #[tracing::instrument]
+    async fn get_bucket_logging(
+        &self,
+        req: S3Request<GetBucketLoggingInput>,
+    ) -> S3Result<S3Response<GetBucketLoggingOutput>> {
+        self.not_configured(
+            "get_bucket_logging",
+            &req.input.bucket,
+            Ok(GetBucketLoggingOutput::default()),
+        )
+        .await
+    }
+
+    // #[tracing::instrument]
+    async fn get_bucket_policy(
+        &self,
+        _req: S3Request<GetBucketPolicyInput>,
+    ) -> S3Result<S3Response<GetBucketPolicyOutput>> {
+        not_implemented("get_bucket_policy", "GetBucketPolicy")
+    }
+
+    // #[tracing::instrument]
+    async fn get_bucket_replication(
+        &self,
+        req: S3Request<GetBucketReplicationInput>,
+    ) -> S3Result<S3Response<GetBucketReplicationOutput>> {
+        self.not_configured(
+            "get_bucket_replication",
+            &req.input.bucket,
+            Err(s3_error!(ReplicationConfigurationNotFoundError)),
+        )
+        .await
+    }
+
+    // #[tracing::instrument]
+    async fn get_bucket_request_payment(
+        &self,
+        req: S3Request<GetBucketRequestPaymentInput>,
+    ) -> S3Result<S3Response<GetBucketRequestPaymentOutput>> {
+        self.not_configured(
+            "get_bucket_request_payment",
+            &req.input.bucket,
+            Ok(GetBucketRequestPaymentOutput {
+                payer: Some(Payer::from_static(Payer::BUCKET_OWNER)),
+            }),
+        )
+        .await
+    }
+
+    // #[tracing::instrument]
+    async fn get_bucket_tagging(
+        &self,
+        req: S3Request<GetBucketTaggingInput>,
+    ) -> S3Result<S3Response<GetBucketTaggingOutput>> {
+        let mut action_counter = self.action_counter("get_bucket_tagging", &req.input.bucket);
+        let bucket =
+            self.read_bucket_path(&req.headers, req.credentials.as_ref(), &req.input.bucket)?;
+
+        let Some((_, metadata)) = self.get_bucket_metadata(&bucket).await? else {
+            return Err(s3_error!(NoSuchBucket));
+        };
+
+        // Buckets can't be tagged, so expose their stored metadata as read-only tags instead.
+        let mut tag_set: TagSet = metadata
+            .into_iter()
+            .map(|(key, value)| Tag { key, value })
+            .collect();
+        tag_set.push(Tag {
+            key: OWNER_TAG_KEY.to_string(),
+            value: bucket.owner().to_string(),
+        });
+        tag_set.sort_by(|a, b| a.key.cmp(&b.key));
+
+        action_counter.success = true;
+        Ok(S3Response::new(GetBucketTaggingOutput { tag_set }))
+    }
+
+    // #[tracing::instrument]
+    async fn get_bucket_website(
+        &self,
+        req: S3Request<GetBucketWebsiteInput>,
+    ) -> S3Result<S3Response<GetBucketWebsiteOutput>> {
+        self.not_configured(
+            "get_bucket_website",
+            &req.input.bucket,
+            Err(s3_error!(NoSuchWebsiteConfiguration)),
+        )
+        .await
+    }
+
     //#[tracing::instrument]
     async fn get_object(
         &self,
         req: S3Request<GetObjectInput>,
     ) -> S3Result<S3Response<GetObjectOutput>> {
-        let mut action_counter = S3ActionCounter::new("get_object");
+        let mut action_counter = self.action_counter("get_object", &req.input.bucket);
         let input = req.input;
-        let bucket = self.get_bucket_path(&input.bucket)?;
+        let bucket =
+            self.read_bucket_path(&req.headers, req.credentials.as_ref(), &input.bucket)?;
 
         let Some(address) = self.get_bucket_address_by_alias(&bucket).await? else {
             return Err(s3_error!(NoSuchBucket));
@@ -550,50 +1675,80 @@ where
             .await
             .map_err(|e| S3Error::new(S3ErrorCode::Custom(ByteString::from(e.to_string()))))?;
 
-        let object = self.get_object(&machine, &input.key).await?;
+        let key = self.backend_key(req.credentials.as_ref(), &bucket, &input.key);
+        let object = self.get_object(&machine, &key).await?;
         let file_len = object.size;
 
-        let (content_length, content_range) = match input.range {
-            None => (file_len, None),
-            Some(range) => {
-                let file_range = range.check(file_len)?;
-                let content_length = file_range.end - file_range.start;
-                let content_range =
-                    fmt_content_range(file_range.start, file_range.end - 1, file_len);
-                (content_length, Some(content_range))
-            }
+        // The download below looks the object up again: the backend resolves a key to its blob
+        // itself and can't be handed the state fetched here. The range is at least resolved
+        // against the size we already have, so the backend gets absolute offsets.
+        //
+        // Ranges of encoded objects would be offsets into the encoded bytes, which transforms
+        // can't map back to the client's view, so the whole object is served instead. HTTP lets
+        // a server ignore `Range`, and clients handle the full `200` response.
+        let encoded = object.metadata.contains_key(PLAINTEXT_LENGTH_METADATA_KEY);
+        let requested_range = input.range.filter(|_| !encoded);
+        let (content_length, content_range, range) = resolve_range(requested_range, file_len)?;
+
+        // The pipe applies backpressure: once the client stops reading and the buffer fills up,
+        // the download task waits rather than pulling the object into memory. Neither side
+        // blocks a thread, so a stalled client holds the buffer and a pending task, nothing more.
+        let (writer, reader) = tokio::io::duplex(self.download_buffer_size);
+
+        let bucket_name = bucket.name();
+        let ctx = TransformContext {
+            bucket: &bucket_name,
+            key: &input.key,
+            metadata: &object.metadata,
         };
-
-        let content_length_i64 = try_!(i64::try_from(content_length));
-
-        let range = match input.range {
-            Some(Range::Int { first, last }) => Some(format!(
-                "{}-{}",
-                first,
-                last.map_or(String::new(), |v| v.to_string())
-            )),
-            Some(Range::Suffix { length }) => Some(format!("-{length}")),
-            _ => None,
+        // Compression is undone first, so transforms see the bytes the client stored.
+        let reader: BoxReader = Box::pin(reader);
+        let (reader, content_length) = match compression(&object.metadata) {
+            Some(compression) => (
+                try_!(decompress_reader(compression, reader)),
+                logical_size(file_len, &object.metadata),
+            ),
+            None => (reader, content_length),
+        };
+        let (reader, content_length) =
+            apply_transforms(&self.transforms, &ctx, reader, content_length);
+        // Transforms that can't size their output up front fall back to the recorded length.
+        let content_length = content_length.or_else(|| {
+            if requested_range.is_some() {
+                return None;
+            }
+            object
+                .metadata
+                .get(PLAINTEXT_LENGTH_METADATA_KEY)?
+                .parse()
+                .ok()
+        });
+        let content_length_i64 = match content_length {
+            Some(len) => Some(try_!(i64::try_from(len))),
+            None => None,
         };
-
-        let (writer, reader) = tokio::io::duplex(4096);
         let reader_stream = ReaderStream::new(reader);
 
         let provider = self.provider.clone();
-        tokio::spawn(async move {
-            let _ = machine
-                .get(
+        let timeout = self.object_api_timeout;
+        // The download is tied to the response body: if the client goes away the body is
+        // dropped and the task aborted, instead of pulling the rest of the object for nothing.
+        let download = tokio::spawn(async move {
+            let _ = object_api_call(
+                timeout,
+                machine.get(
                     provider.deref(),
-                    input.key.as_str(),
+                    key.as_str(),
                     writer,
                     GetOptions {
                         range,
                         height: FvmQueryHeight::Committed,
                         show_progress: false,
                     },
-                )
-                .await
-                .map_err(|err| error!("failed to download object: {}", err));
+                ),
+            )
+            .await
+            .map_err(|err| error!("failed to download object: {}", err));
         });
 
         let last_modified = object
@@ -606,35 +1761,208 @@ where
             .get(ETAG_METADATA_KEY)
             .map(|v| v.to_string());
 
+        let (object_lock_mode, object_lock_retain_until_date, object_lock_legal_hold_status) =
+            object_lock_fields(&object.metadata);
+
         let output = GetObjectOutput {
-            body: Some(StreamingBlob::wrap(reader_stream)),
-            content_length: Some(content_length_i64),
+            body: Some(StreamingBlob::wrap(AbortOnDrop::new(
+                reader_stream,
+                download,
+            ))),
+            content_length: content_length_i64,
             e_tag,
             content_range,
+            content_type: Some(content_type(&object.metadata)),
             last_modified,
+            metadata: user_metadata(&object.metadata),
+            object_lock_legal_hold_status,
+            object_lock_mode,
+            object_lock_retain_until_date,
+            storage_class: Some(StorageClass::from(
+                storage_class(&object.metadata, &self.storage_class).to_string(),
+            )),
+            version_id: Some(NULL_VERSION_ID.to_string()),
+            ..Default::default()
+        };
+        action_counter.success = true;
+        Ok(S3Response::new(output))
+    }
+
+    // #[tracing::instrument]
+    async fn get_object_acl(
+        &self,
+        req: S3Request<GetObjectAclInput>,
+    ) -> S3Result<S3Response<GetObjectAclOutput>> {
+        let mut action_counter = self.action_counter("get_object_acl", &req.input.bucket);
+        let input = req.input;
+        let bucket =
+            self.read_bucket_path(&req.headers, req.credentials.as_ref(), &input.bucket)?;
+
+        let Some(address) = self.get_bucket_address_by_alias(&bucket).await? else {
+            return Err(s3_error!(NoSuchBucket));
+        };
+
+        let machine = Bucket::attach(address)
+            .await
+            .map_err(|e| S3Error::new(S3ErrorCode::Custom(ByteString::from(e.to_string()))))?;
+
+        let _ = self
+            .get_object(
+                &machine,
+                &self.backend_key(req.credentials.as_ref(), &bucket, &input.key),
+            )
+            .await?;
+
+        // Objects have no ACLs of their own; the bucket owner always has full control.
+        let owner = bucket.owner().to_string();
+        let output = GetObjectAclOutput {
+            grants: Some(vec![Grant {
+                grantee: Some(Grantee {
+                    display_name: Some(owner.clone()),
+                    email_address: None,
+                    id: Some(owner.clone()),
+                    type_: Type::from_static(Type::CANONICAL_USER),
+                    uri: None,
+                }),
+                permission: Some(Permission::from_static(Permission::FULL_CONTROL)),
+            }]),
+            owner: Some(Owner {
+                display_name: Some(owner.clone()),
+                id: Some(owner),
+            }),
             ..Default::default()
         };
         action_counter.success = true;
         Ok(S3Response::new(output))
     }
 
+    // #[tracing::instrument]
+    async fn get_object_legal_hold(
+        &self,
+        req: S3Request<GetObjectLegalHoldInput>,
+    ) -> S3Result<S3Response<GetObjectLegalHoldOutput>> {
+        let mut action_counter = self.action_counter("get_object_legal_hold", &req.input.bucket);
+        let bucket =
+            self.read_bucket_path(&req.headers, req.credentials.as_ref(), &req.input.bucket)?;
+        let (_, _, object) = self
+            .lock_target(req.credentials.as_ref(), &bucket, &req.input.key)
+            .await?;
+
+        let status = if legal_hold(&object.metadata) {
+            ObjectLockLegalHoldStatus::ON
+        } else {
+            ObjectLockLegalHoldStatus::OFF
+        };
+        let output = GetObjectLegalHoldOutput {
+            legal_hold: Some(ObjectLockLegalHold {
+                status: Some(ObjectLockLegalHoldStatus::from_static(status)),
+            }),
+        };
+        action_counter.success = true;
+        Ok(S3Response::new(output))
+    }
+
+    // #[tracing::instrument]
+    async fn get_object_lock_configuration(
+        &self,
+        req: S3Request<GetObjectLockConfigurationInput>,
+    ) -> S3Result<S3Response<GetObjectLockConfigurationOutput>> {
+        self.not_configured(
+            "get_object_lock_configuration",
+            &req.input.bucket,
+            Err(s3_error!(ObjectLockConfigurationNotFoundError)),
+        )
+        .await
+    }
+
+    // #[tracing::instrument]
+    async fn get_object_retention(
+        &self,
+        req: S3Request<GetObjectRetentionInput>,
+    ) -> S3Result<S3Response<GetObjectRetentionOutput>> {
+        let mut action_counter = self.action_counter("get_object_retention", &req.input.bucket);
+        let bucket =
+            self.read_bucket_path(&req.headers, req.credentials.as_ref(), &req.input.bucket)?;
+        let (_, _, object) = self
+            .lock_target(req.credentials.as_ref(), &bucket, &req.input.key)
+            .await?;
+
+        let (Some(mode), Some(retain_until_date), _) = object_lock_fields(&object.metadata) else {
+            return Err(s3_error!(NoSuchObjectLockConfiguration));
+        };
+        let output = GetObjectRetentionOutput {
+            retention: Some(ObjectLockRetention {
+                mode: Some(ObjectLockRetentionMode::from(mode.as_str().to_string())),
+                retain_until_date: Some(retain_until_date),
+            }),
+        };
+        action_counter.success = true;
+        Ok(S3Response::new(output))
+    }
+
+    // #[tracing::instrument]
+    async fn get_object_tagging(
+        &self,
+        _req: S3Request<GetObjectTaggingInput>,
+    ) -> S3Result<S3Response<GetObjectTaggingOutput>> {
+        not_implemented("get_object_tagging", "GetObjectTagging")
+    }
+
     // #[tracing::instrument]
     async fn head_bucket(
         &self,
         req: S3Request<HeadBucketInput>,
     ) -> S3Result<S3Response<HeadBucketOutput>> {
-        let mut action_counter = S3ActionCounter::new("head_bucket");
+        let mut action_counter = self.action_counter("head_bucket", &req.input.bucket);
         let input = req.input;
-        let bucket = self.get_bucket_path(&input.bucket)?;
+        let bucket =
+            self.read_bucket_path(&req.headers, req.credentials.as_ref(), &input.bucket)?;
 
-        let Some(_) = self.get_bucket_address_by_alias(&bucket).await? else {
+        let Some(address) = self.get_bucket_address_by_alias(&bucket).await? else {
             return Err(s3_error!(NoSuchBucket));
         };
 
-        action_counter.success = true;
-        Ok(S3Response::new(HeadBucketOutput {
+        // Counting lists the whole bucket, so it's only done when asked for. Tenants only count
+        // their own namespace.
+        let stats = if req
+            .headers
+            .get(STATS_HEADER)
+            .is_some_and(|v| v.as_bytes().eq_ignore_ascii_case(b"true"))
+        {
+            let prefix = match self.key_prefix(req.credentials.as_ref()) {
+                "" => String::new(),
+                tenant_prefix => self
+                    .key_mapping
+                    .backend_prefix(&bucket.name(), tenant_prefix)
+                    .ok_or_else(|| {
+                        s3_error!(
+                            NotImplemented,
+                            "tenant stats aren't supported by this bucket's key mapping"
+                        )
+                    })?,
+            };
+            Some(self.bucket_stats(address, &prefix).await?)
+        } else {
+            None
+        };
+
+        let region = self
+            .region
+            .clone()
+            .unwrap_or_else(|| DEFAULT_REGION.to_string());
+
+        let mut resp = S3Response::new(HeadBucketOutput {
+            bucket_region: Some(region),
             ..Default::default()
-        }))
+        });
+        if let Some(stats) = stats {
+            resp.headers
+                .insert(OBJECT_COUNT_HEADER, HeaderValue::from(stats.objects));
+            resp.headers
+                .insert(TOTAL_SIZE_HEADER, HeaderValue::from(stats.bytes));
+        }
+        action_counter.success = true;
+        Ok(resp)
     }
 
     // #[tracing::instrument]
@@ -642,9 +1970,10 @@ where
         &self,
         req: S3Request<HeadObjectInput>,
     ) -> S3Result<S3Response<HeadObjectOutput>> {
-        let mut action_counter = S3ActionCounter::new("head_object");
+        let mut action_counter = self.action_counter("head_object", &req.input.bucket);
         let input = req.input;
-        let bucket = self.get_bucket_path(&input.bucket)?;
+        let bucket =
+            self.read_bucket_path(&req.headers, req.credentials.as_ref(), &input.bucket)?;
 
         let Some(address) = self.get_bucket_address_by_alias(&bucket).await? else {
             return Err(s3_error!(NoSuchBucket));
@@ -654,49 +1983,67 @@ where
             .await
             .map_err(|e| S3Error::new(S3ErrorCode::Custom(ByteString::from(e.to_string()))))?;
 
-        let object_list = machine
-            .query(
-                self.provider.deref(),
-                QueryOptions {
-                    prefix: input.key.clone(),
-                    ..Default::default()
-                },
-            )
-            .await
-            .map_err(|e| S3Error::new(S3ErrorCode::Custom(ByteString::from(e.to_string()))))?;
+        let key = self.backend_key(req.credentials.as_ref(), &bucket, &input.key);
+        let object_state = self.get_object(&machine, &key).await?;
 
-        let object_state = if let Some((_, object_state)) = object_list.objects.into_iter().next() {
-            object_state
-        } else {
-            return Err(s3_error!(NoSuchKey));
+        // Clients may probe a range with HEAD, so answer with what a GET of it would return.
+        // Encoded objects are served whole, as by GET.
+        let encoded = object_state
+            .metadata
+            .contains_key(PLAINTEXT_LENGTH_METADATA_KEY);
+        let (content_length, content_range) = match input.range.filter(|_| !encoded) {
+            Some(range) => {
+                let (content_length, content_range, _) =
+                    resolve_range(Some(range), object_state.size)?;
+                (content_length, content_range)
+            }
+            None => (
+                logical_size(object_state.size, &object_state.metadata),
+                None,
+            ),
         };
+        let content_length_i64 = try_!(i64::try_from(content_length));
 
-        let content_length_i64 = try_!(i64::try_from(object_state.size));
-
-        // TODO: detect content type
-        let content_type = mime::APPLICATION_OCTET_STREAM;
         let last_modified = object_state
             .metadata
             .get(LAST_MODIFIED_METADATA_KEY)
             .map(|v| Timestamp::parse(TimestampFormat::EpochSeconds, v.as_str()).unwrap());
 
+        let (object_lock_mode, object_lock_retain_until_date, object_lock_legal_hold_status) =
+            object_lock_fields(&object_state.metadata);
+
         let output = HeadObjectOutput {
+            accept_ranges: Some("bytes".to_string()),
             content_length: Some(content_length_i64),
-            content_type: Some(content_type),
+            content_type: Some(content_type(&object_state.metadata)),
+            e_tag: object_state.metadata.get(ETAG_METADATA_KEY).cloned(),
             last_modified,
-            metadata: None,
+            metadata: user_metadata(&object_state.metadata),
+            object_lock_legal_hold_status,
+            object_lock_mode,
+            object_lock_retain_until_date,
+            storage_class: Some(StorageClass::from(
+                storage_class(&object_state.metadata, &self.storage_class).to_string(),
+            )),
+            version_id: Some(NULL_VERSION_ID.to_string()),
             ..Default::default()
         };
+        let mut resp = S3Response::new(output);
+        if let Some(content_range) = content_range {
+            resp.status = Some(StatusCode::PARTIAL_CONTENT);
+            resp.headers
+                .insert(CONTENT_RANGE, try_!(HeaderValue::try_from(content_range)));
+        }
         action_counter.success = true;
-        Ok(S3Response::new(output))
+        Ok(resp)
     }
 
     // #[tracing::instrument]
     async fn list_buckets(
         &self,
-        _: S3Request<ListBucketsInput>,
+        req: S3Request<ListBucketsInput>,
     ) -> S3Result<S3Response<ListBucketsOutput>> {
-        let mut action_counter = S3ActionCounter::new("list_buckets");
+        let mut action_counter = S3ActionCounter::new("list_buckets", None);
         if self.is_read_only {
             return Err(s3_error!(
                 NotImplemented,
@@ -704,6 +2051,28 @@ where
             ));
         }
 
+        // ListBucketsInput predates the paginated ListBuckets API, so read its parameters from
+        // the query string.
+        let params: HashMap<String, String> = req
+            .uri
+            .query()
+            .map(|q| {
+                url::form_urlencoded::parse(q.as_bytes())
+                    .into_owned()
+                    .collect()
+            })
+            .unwrap_or_default();
+        let prefix = params.get("prefix").map_or("", String::as_str);
+        let max_buckets = match params.get("max-buckets") {
+            Some(v) => v
+                .parse::<usize>()
+                .ok()
+                .filter(|v| (1..=MAX_LIST_BUCKETS).contains(v))
+                .ok_or_else(|| s3_error!(InvalidArgument, "invalid max-buckets"))?,
+            None => usize::MAX,
+        };
+        let continuation_token = params.get("continuation-token");
+
         let wallet = match &self.wallet {
             Some(w) => w.clone(),
             None => unreachable!(),
@@ -724,21 +2093,45 @@ where
                 .metadata
                 .get(ALIAS_METADATA_KEY)
                 .cloned()
-                .or(Some(data.address.to_string()));
+                .unwrap_or_else(|| data.address.to_string());
+
+            if !name.starts_with(prefix)
+                || continuation_token.is_some_and(|token| name.as_str() <= token.as_str())
+            {
+                continue;
+            }
 
             let bucket = s3s::dto::Bucket {
-                name,
+                name: Some(name),
                 creation_date,
             };
             buckets.push(bucket);
         }
 
+        // Pages are ordered by name so the last name returned can be used as the token.
+        buckets.sort_by(|a, b| a.name.cmp(&b.name));
+        let next_continuation_token = if buckets.len() > max_buckets {
+            buckets.truncate(max_buckets);
+            buckets.last().and_then(|b| b.name.clone())
+        } else {
+            None
+        };
+
         let output = ListBucketsOutput {
             buckets: Some(buckets),
             owner: None,
         };
+        let mut resp = S3Response::new(output);
+        // ListBucketsOutput has no continuation token field either, so it goes in a header.
+        if let Some(token) = next_continuation_token {
+            resp.headers.insert(
+                CONTINUATION_TOKEN_HEADER,
+                try_!(HeaderValue::try_from(token)),
+            );
+        }
+
         action_counter.success = true;
-        Ok(S3Response::new(output))
+        Ok(resp)
     }
 
     // #[tracing::instrument]
@@ -746,22 +2139,40 @@ where
         &self,
         req: S3Request<ListObjectsInput>,
     ) -> S3Result<S3Response<ListObjectsOutput>> {
-        let mut action_counter = S3ActionCounter::new("list_objects");
+        let mut action_counter = self.action_counter("list_objects", &req.input.bucket);
+        // The marker becomes the v2 listing's `start_after`, so pages resume after the last key
+        // or common prefix of the previous one.
+        let marker = req.input.marker.clone();
+        let hard_limit = i32::try_from(self.max_keys_hard_limit).unwrap_or(i32::MAX);
+        let max_keys = req.input.max_keys.map_or(hard_limit, |v| v.min(hard_limit));
         let v2_resp = self.list_objects_v2(req.map_input(Into::into)).await?;
 
         action_counter.success = true;
-        Ok(v2_resp.map_output(|v2| ListObjectsOutput {
-            contents: v2.contents,
-            delimiter: v2.delimiter,
-            encoding_type: v2.encoding_type,
-            name: v2.name,
-            prefix: v2.prefix,
-            common_prefixes: v2.common_prefixes,
-            max_keys: v2.max_keys,
-            is_truncated: v2.is_truncated,
-            marker: v2.continuation_token,
-            next_marker: v2.next_continuation_token,
-            ..Default::default()
+        Ok(v2_resp.map_output(|v2| {
+            let last_key = v2.contents.iter().flatten().filter_map(|o| o.key.clone());
+            let last_prefix = v2
+                .common_prefixes
+                .iter()
+                .flatten()
+                .filter_map(|p| p.prefix.clone());
+            let next_marker = v2
+                .is_truncated
+                .unwrap_or_default()
+                .then(|| last_key.chain(last_prefix).max())
+                .flatten();
+            ListObjectsOutput {
+                contents: v2.contents,
+                delimiter: v2.delimiter,
+                encoding_type: v2.encoding_type,
+                name: v2.name,
+                prefix: v2.prefix,
+                common_prefixes: v2.common_prefixes,
+                max_keys: Some(max_keys),
+                is_truncated: v2.is_truncated,
+                marker,
+                next_marker,
+                ..Default::default()
+            }
         }))
     }
 
@@ -770,9 +2181,10 @@ where
         &self,
         req: S3Request<ListObjectsV2Input>,
     ) -> S3Result<S3Response<ListObjectsV2Output>> {
-        let mut action_counter = S3ActionCounter::new("list_objects_v2");
+        let mut action_counter = self.action_counter("list_objects_v2", &req.input.bucket);
         let input: ListObjectsV2Input = req.input;
-        let bucket = self.get_bucket_path(&input.bucket)?;
+        let bucket =
+            self.read_bucket_path(&req.headers, req.credentials.as_ref(), &input.bucket)?;
 
         let Some(address) = self.get_bucket_address_by_alias(&bucket).await? else {
             return Err(s3_error!(NoSuchBucket));
@@ -782,9 +2194,23 @@ where
             .await
             .map_err(|e| S3Error::new(S3ErrorCode::Custom(ByteString::from(e.to_string()))))?;
 
-        let prefix = match &input.prefix {
-            Some(prefix) => prefix.to_string(),
-            None => String::new(),
+        let bucket_name = bucket.name();
+        // Tenants only ever see their own namespace, with its prefix stripped from the keys.
+        let tenant_prefix = self.key_prefix(req.credentials.as_ref());
+        let prefix = match format!(
+            "{tenant_prefix}{}",
+            input.prefix.as_deref().unwrap_or_default()
+        ) {
+            prefix if prefix.is_empty() => prefix,
+            prefix => self
+                .key_mapping
+                .backend_prefix(&bucket_name, &prefix)
+                .ok_or_else(|| {
+                    s3_error!(
+                        NotImplemented,
+                        "listing by prefix isn't supported by this bucket's key mapping"
+                    )
+                })?,
         };
 
         let delimiter = match &input.delimiter {
@@ -794,21 +2220,33 @@ where
 
         let limit = input
             .max_keys
-            .map_or(MAX_LIST_OBJECTS_KEYS, |v| {
-                v.try_into().unwrap_or(MAX_LIST_OBJECTS_KEYS)
+            .map_or(self.max_keys_hard_limit, |v| {
+                v.try_into().unwrap_or(self.max_keys_hard_limit)
             })
-            .min(MAX_LIST_OBJECTS_KEYS);
-        let start_key = input
-            .continuation_token
-            .as_ref()
-            .map(|v| v.as_bytes().to_vec());
+            .min(self.max_keys_hard_limit);
+        let start_key = match (&input.continuation_token, &input.start_after) {
+            (Some(token), _) => Some(decode_continuation_token(token)?),
+            (None, Some(start_after)) => {
+                let start_after = self
+                    .key_mapping
+                    .backend_prefix(&bucket_name, &format!("{tenant_prefix}{start_after}"))
+                    .ok_or_else(|| {
+                        s3_error!(
+                            NotImplemented,
+                            "listing after a key isn't supported by this bucket's key mapping"
+                        )
+                    })?;
+                Some(key_after(start_after.into_bytes(), &delimiter))
+            }
+            (None, None) => None,
+        };
 
         let response = machine
             .query(
                 self.provider.deref(),
                 QueryOptions {
-                    prefix,
-                    delimiter,
+                    prefix: prefix.clone(),
+                    delimiter: delimiter.clone(),
                     start_key,
                     limit,
                     ..Default::default()
@@ -817,9 +2255,45 @@ where
             .await
             .map_err(|e| S3Error::new(S3ErrorCode::Custom(ByteString::from(e.to_string()))))?;
 
+        // A full page may come back with a next key even when nothing follows it. Probe past the
+        // page boundary so the last page isn't reported as truncated.
+        let mut next_key = response.next_key;
+        let page_len = response.objects.len() + response.common_prefixes.len();
+        if next_key.is_some() && try_!(u64::try_from(page_len)) >= limit {
+            let probe = machine
+                .query(
+                    self.provider.deref(),
+                    QueryOptions {
+                        prefix,
+                        delimiter,
+                        start_key: next_key.clone(),
+                        limit: 1,
+                        ..Default::default()
+                    },
+                )
+                .await
+                .map_err(|e| S3Error::new(S3ErrorCode::Custom(ByteString::from(e.to_string()))))?;
+            if probe.objects.is_empty() && probe.common_prefixes.is_empty() {
+                next_key = None;
+            }
+        }
+
+        let url_encoded = input
+            .encoding_type
+            .as_ref()
+            .is_some_and(|v| v.as_str() == EncodingType::URL);
+
         let mut objects: Vec<Object> = Vec::new();
         for (key, object_state) in response.objects {
-            let key_str = String::from_utf8_lossy(&key);
+            // Parts of uploads in progress aren't objects yet.
+            if key.starts_with(UPLOAD_PARTS_PREFIX.as_bytes()) {
+                continue;
+            }
+            let key = self.key_mapping.from_backend(&bucket_name, &key);
+            let Some(key) = key.strip_prefix(tenant_prefix.as_bytes()) else {
+                continue;
+            };
+            let key_str = encode_key(key, url_encoded)?;
 
             let last_modified = object_state
                 .metadata
@@ -827,39 +2301,56 @@ where
                 .map(|v| Timestamp::parse(TimestampFormat::EpochSeconds, v.as_str()).unwrap());
 
             objects.push(Object {
-                key: Some(key_str.to_string()),
+                key: Some(key_str),
                 last_modified,
-                size: Some(try_!(i64::try_from(object_state.size))),
+                size: Some(try_!(i64::try_from(logical_size(
+                    object_state.size,
+                    &object_state.metadata
+                )))),
+                storage_class: Some(ObjectStorageClass::from(
+                    storage_class(&object_state.metadata, &self.storage_class).to_string(),
+                )),
                 ..Default::default()
             });
         }
 
         let mut common_prefixes: CommonPrefixList = Vec::new();
         for common_prefix in response.common_prefixes {
-            let s = try_!(String::from_utf8(common_prefix));
+            if common_prefix.starts_with(UPLOAD_PARTS_PREFIX.as_bytes()) {
+                continue;
+            }
+            let common_prefix = self.key_mapping.from_backend(&bucket_name, &common_prefix);
+            let Some(common_prefix) = common_prefix.strip_prefix(tenant_prefix.as_bytes()) else {
+                continue;
+            };
+            let s = encode_key(common_prefix, url_encoded)?;
             common_prefixes.push(CommonPrefix { prefix: Some(s) });
         }
 
         let key_count = try_!(i32::try_from(objects.len()));
-        let next_continuation_token = response
-            .next_key
-            .map(|key| String::from_utf8_lossy(&key).into());
+        // Echo the page size asked for, within the hard limit, as v1 listings do.
+        let max_keys = try_!(i32::try_from(limit));
+        let next_continuation_token = next_key.map(hex);
 
         let output = ListObjectsV2Output {
             key_count: Some(key_count),
-            max_keys: Some(
-                i32::try_from(MAX_LIST_OBJECTS_KEYS)
-                    .expect("MAX_LIST_OBJECTS_KEYS must fit into i32"),
-            ),
+            max_keys: Some(max_keys),
             contents: Some(objects),
-            delimiter: input.delimiter,
+            delimiter: input
+                .delimiter
+                .map(|v| encode_key(v.as_bytes(), url_encoded))
+                .transpose()?,
             common_prefixes: Some(common_prefixes),
             encoding_type: input.encoding_type,
             name: Some(bucket.name()),
-            prefix: input.prefix,
+            prefix: input
+                .prefix
+                .map(|v| encode_key(v.as_bytes(), url_encoded))
+                .transpose()?,
             is_truncated: next_continuation_token.is_some().into(),
             continuation_token: input.continuation_token,
             next_continuation_token,
+            start_after: input.start_after,
             ..Default::default()
         };
 
@@ -867,26 +2358,88 @@ where
         Ok(S3Response::new(output))
     }
 
+    // #[tracing::instrument]
+    async fn put_bucket_cors(
+        &self,
+        _req: S3Request<PutBucketCorsInput>,
+    ) -> S3Result<S3Response<PutBucketCorsOutput>> {
+        not_implemented("put_bucket_cors", "PutBucketCors")
+    }
+
+    // #[tracing::instrument]
+    async fn put_bucket_lifecycle_configuration(
+        &self,
+        _req: S3Request<PutBucketLifecycleConfigurationInput>,
+    ) -> S3Result<S3Response<PutBucketLifecycleConfigurationOutput>> {
+        not_implemented(
+            "put_bucket_lifecycle_configuration",
+            "PutBucketLifecycleConfiguration",
+        )
+    }
+
+    // #[tracing::instrument]
+    async fn put_bucket_policy(
+        &self,
+        _req: S3Request<PutBucketPolicyInput>,
+    ) -> S3Result<S3Response<PutBucketPolicyOutput>> {
+        not_implemented("put_bucket_policy", "PutBucketPolicy")
+    }
+
+    // #[tracing::instrument]
+    async fn put_bucket_replication(
+        &self,
+        _req: S3Request<PutBucketReplicationInput>,
+    ) -> S3Result<S3Response<PutBucketReplicationOutput>> {
+        not_implemented("put_bucket_replication", "PutBucketReplication")
+    }
+
+    // #[tracing::instrument]
+    async fn put_bucket_tagging(
+        &self,
+        _req: S3Request<PutBucketTaggingInput>,
+    ) -> S3Result<S3Response<PutBucketTaggingOutput>> {
+        not_implemented("put_bucket_tagging", "PutBucketTagging")
+    }
+
     // #[tracing::instrument]
     async fn put_object(
         &self,
         req: S3Request<PutObjectInput>,
     ) -> S3Result<S3Response<PutObjectOutput>> {
-        let mut action_counter = S3ActionCounter::new("put_object");
+        let mut action_counter = self.action_counter("put_object", &req.input.bucket);
         if self.is_read_only {
-            return Err(s3_error!(
-                NotImplemented,
-                "PutObject is not implemented in read-only mode"
-            ));
+            return read_only("PutObject");
         }
 
+        let precondition = write_precondition(&req.headers)?;
+        let compression = storage_compression(&req.headers)?;
+        let bypass_governance = bypass_governance(&req.headers);
+
         let input = req.input;
 
         let PutObjectInput {
             body, bucket, key, ..
         } = input;
 
-        let bucket = self.get_bucket_path(&bucket)?;
+        if let Some(reserved) = input
+            .metadata
+            .iter()
+            .flatten()
+            .map(|(k, _)| k)
+            .find(|k| is_reserved(k))
+        {
+            return Err(s3_error!(
+                InvalidArgument,
+                "metadata key {reserved} is reserved"
+            ));
+        }
+        check_object_key(&key, self.safe_object_keys)?;
+        self.check_storage_class(input.storage_class.as_ref())?;
+
+        // Everything that can reject the request must happen before the body is polled. Clients
+        // sending `Expect: 100-continue` then get the error instead of a 100 Continue, and
+        // never upload the body.
+        let bucket = self.write_bucket_path(&bucket)?;
 
         let Some(address) = self.get_bucket_address_by_alias(&bucket).await? else {
             return Err(s3_error!(NoSuchBucket));
@@ -900,97 +2453,427 @@ where
             .await
             .map_err(|e| S3Error::new(S3ErrorCode::Custom(ByteString::from(e.to_string()))))?;
 
-        let mut file = try_!(TempFile::new().await);
+        let backend_key = self.backend_key(req.credentials.as_ref(), &bucket, &key);
+        self.check_unlocked(&machine, &backend_key, bypass_governance)
+            .await?;
 
-        let mut md5_hash = <Md5 as Digest>::new();
-        while let Some(Ok(v)) = body.next().await {
-            md5_hash.update(v.as_ref());
+        let _create_claim = self
+            .check_write_precondition(precondition.as_ref(), address, &machine, &backend_key)
+            .await?;
+
+        let mut file = try_!(TempFile::new_in(self.temp_dir.as_path()).await);
+
+        let mut e_tag_hash = self.etag_algorithm.hasher();
+        let mut compressor = match compression {
+            Some(compression) => Some(try_!(compression.compressor())),
+            None => None,
+        };
+        // The etag and length are the client's bytes; the quota is charged what's stored.
+        let mut size: u64 = 0;
+        let mut stored_size: u64 = 0;
+        while let Some(v) = body.next().await {
+            let v = v.map_err(|e| S3Error::with_source(S3ErrorCode::IncompleteBody, e))?;
+            e_tag_hash.update(v.as_ref());
+            size += v.len() as u64;
+            let v = match &mut compressor {
+                Some(compressor) => Bytes::from(try_!(compressor.push(&v))),
+                None => v,
+            };
             try_!(file.write_all(&v).await);
+            stored_size += v.len() as u64;
+        }
+        if let Some(compressor) = compressor {
+            let rest = try_!(compressor.finish());
+            try_!(file.write_all(&rest).await);
+            stored_size += rest.len() as u64;
         }
         try_!(file.flush().await);
         try_!(file.rewind().await);
 
+        if let Some(content_length) = input.content_length {
+            if u64::try_from(content_length).ok() != Some(size) {
+                return Err(s3_error!(
+                    IncompleteBody,
+                    "received {size} bytes but Content-Length is {content_length}"
+                ));
+            }
+        }
+
         let mut wallet = match &self.wallet {
             Some(w) => w.clone(),
             None => unreachable!(),
         };
 
-        let md5_sum = hex(md5_hash.finalize());
-        let e_tag = format!("\"{md5_sum}\"");
+        let replaced = self.replaced_charge(&machine, &backend_key).await?;
+        let reservation =
+            self.reserve_quota(req.credentials.as_ref(), stored_size, replaced.as_ref())?;
 
-        let last_modified = try_!(SystemTime::now().duration_since(UNIX_EPOCH)).as_secs();
+        let e_tag = e_tag_hash.e_tag();
+
+        let last_modified = try_!(self.clock.now().duration_since(UNIX_EPOCH)).as_secs();
         let mut metadata = HashMap::from([
             (
                 LAST_MODIFIED_METADATA_KEY.to_string(),
                 last_modified.to_string(),
             ),
             (ETAG_METADATA_KEY.to_string(), e_tag.to_string()),
+            (
+                STORAGE_CLASS_METADATA_KEY.to_string(),
+                self.storage_class.clone(),
+            ),
         ]);
+        if let Some(content_type) = input.content_type {
+            metadata.insert(
+                CONTENT_TYPE_METADATA_KEY.to_string(),
+                content_type.to_string(),
+            );
+        }
+        if let Some(compression) = compression {
+            metadata.insert(
+                COMPRESSION_METADATA_KEY.to_string(),
+                compression.to_string(),
+            );
+            metadata.insert(PLAINTEXT_LENGTH_METADATA_KEY.to_string(), size.to_string());
+        }
 
         if input.metadata.is_some() {
             for (key, value) in input.metadata.unwrap() {
                 metadata.insert(key, value);
             }
         };
+        if let (Some(mode), Some(retain_until)) =
+            (input.object_lock_mode, input.object_lock_retain_until_date)
+        {
+            let retain_until = parse_retention(mode.as_str(), retain_until, last_modified)?;
+            metadata.insert(
+                RETENTION_MODE_METADATA_KEY.to_string(),
+                mode.as_str().to_string(),
+            );
+            metadata.insert(
+                RETAIN_UNTIL_METADATA_KEY.to_string(),
+                retain_until.to_string(),
+            );
+        }
+        if input
+            .object_lock_legal_hold_status
+            .is_some_and(|v| v.as_str() == ObjectLockLegalHoldStatus::ON)
+        {
+            metadata.insert(
+                LEGAL_HOLD_METADATA_KEY.to_string(),
+                ObjectLockLegalHoldStatus::ON.to_string(),
+            );
+        }
+        if let Some(reservation) = &reservation {
+            metadata.insert(
+                QUOTA_OWNER_METADATA_KEY.to_string(),
+                reservation.access_key().to_string(),
+            );
+        }
 
-        let _tx = machine
-            .add_from_path(
+        let tx = object_api_call(
+            self.object_api_timeout,
+            machine.add_from_path(
                 self.provider.deref(),
                 &mut wallet,
-                &key,
+                &backend_key,
                 file.file_path(),
                 AddOptions {
                     metadata,
                     ..AddOptions::default()
                 },
-            )
-            .await
-            .map_err(|e| S3Error::new(S3ErrorCode::Custom(ByteString::from(e.to_string()))))?;
+            ),
+        )
+        .await?;
+        self.key_written(machine.address(), &backend_key);
+        if let Some(reservation) = reservation {
+            reservation.commit();
+        }
+        self.release_replaced(req.credentials.as_ref(), replaced, stored_size);
 
         let output = PutObjectOutput {
             e_tag: Some(e_tag),
+            version_id: Some(NULL_VERSION_ID.to_string()),
             ..Default::default()
         };
+        let mut resp = S3Response::new(output);
+        set_tx_hash_header(&mut resp, tx.hash());
 
         action_counter.success = true;
-        Ok(S3Response::new(output))
+        Ok(resp)
     }
 
     // #[tracing::instrument]
-    async fn upload_part(
+    async fn put_object_acl(
         &self,
-        req: S3Request<UploadPartInput>,
-    ) -> S3Result<S3Response<UploadPartOutput>> {
-        let mut action_counter = S3ActionCounter::new("upload_part");
+        req: S3Request<PutObjectAclInput>,
+    ) -> S3Result<S3Response<PutObjectAclOutput>> {
+        let mut action_counter = self.action_counter("put_object_acl", &req.input.bucket);
         if self.is_read_only {
+            return read_only("PutObjectAcl");
+        }
+
+        let input = req.input;
+
+        // Only the canned `private` ACL matches what's enforced, so anything else is refused.
+        let is_private = input
+            .acl
+            .as_ref()
+            .is_some_and(|acl| acl.as_str() == ObjectCannedACL::PRIVATE);
+        let has_grants = input.access_control_policy.is_some()
+            || input.grant_full_control.is_some()
+            || input.grant_read.is_some()
+            || input.grant_read_acp.is_some()
+            || input.grant_write.is_some()
+            || input.grant_write_acp.is_some();
+        if !is_private || has_grants {
             return Err(s3_error!(
                 NotImplemented,
-                "UploadPart is not implemented in read-only mode"
+                "only the private canned ACL is supported"
             ));
         }
 
+        let bucket = self.get_bucket_path(&input.bucket)?;
+
+        let Some(address) = self.get_bucket_address_by_alias(&bucket).await? else {
+            return Err(s3_error!(NoSuchBucket));
+        };
+
+        let machine = Bucket::attach(address)
+            .await
+            .map_err(|e| S3Error::new(S3ErrorCode::Custom(ByteString::from(e.to_string()))))?;
+
+        let _ = self
+            .get_object(
+                &machine,
+                &self.backend_key(req.credentials.as_ref(), &bucket, &input.key),
+            )
+            .await?;
+
+        action_counter.success = true;
+        Ok(S3Response::new(PutObjectAclOutput::default()))
+    }
+
+    // #[tracing::instrument]
+    async fn put_object_legal_hold(
+        &self,
+        req: S3Request<PutObjectLegalHoldInput>,
+    ) -> S3Result<S3Response<PutObjectLegalHoldOutput>> {
+        let mut action_counter = self.action_counter("put_object_legal_hold", &req.input.bucket);
+        if self.is_read_only {
+            return read_only("PutObjectLegalHold");
+        }
+
+        let input = req.input;
+        let on = match input.legal_hold.and_then(|v| v.status) {
+            Some(status) if status.as_str() == ObjectLockLegalHoldStatus::ON => true,
+            Some(status) if status.as_str() == ObjectLockLegalHoldStatus::OFF => false,
+            _ => {
+                return Err(s3_error!(
+                    MalformedXML,
+                    "legal hold status must be ON or OFF"
+                ))
+            }
+        };
+        let (address, key, object) = self
+            .lock_target(
+                req.credentials.as_ref(),
+                &self.write_bucket_path(&input.bucket)?,
+                &input.key,
+            )
+            .await?;
+
+        let mut resp = S3Response::new(PutObjectLegalHoldOutput::default());
+        if on != legal_hold(&object.metadata) {
+            let mut metadata = object.metadata.clone();
+            if on {
+                metadata.insert(
+                    LEGAL_HOLD_METADATA_KEY.to_string(),
+                    ObjectLockLegalHoldStatus::ON.to_string(),
+                );
+            } else {
+                metadata.remove(LEGAL_HOLD_METADATA_KEY);
+            }
+            let tx_hash = self.rewrite_lock(address, key, object, metadata).await?;
+            set_tx_hash_header(&mut resp, tx_hash);
+        }
+
+        action_counter.success = true;
+        Ok(resp)
+    }
+
+    // #[tracing::instrument]
+    async fn put_object_retention(
+        &self,
+        req: S3Request<PutObjectRetentionInput>,
+    ) -> S3Result<S3Response<PutObjectRetentionOutput>> {
+        let mut action_counter = self.action_counter("put_object_retention", &req.input.bucket);
+        if self.is_read_only {
+            return read_only("PutObjectRetention");
+        }
+
+        let input = req.input;
+        let (address, key, object) = self
+            .lock_target(
+                req.credentials.as_ref(),
+                &self.write_bucket_path(&input.bucket)?,
+                &input.key,
+            )
+            .await?;
+
+        let now = try_!(self.clock.now().duration_since(UNIX_EPOCH)).as_secs();
+        let retention = input.retention.unwrap_or_default();
+        let new = match (retention.mode, retention.retain_until_date) {
+            (Some(mode), Some(retain_until)) => {
+                let retain_until = parse_retention(mode.as_str(), retain_until, now)?;
+                Some((mode.as_str().to_string(), retain_until))
+            }
+            (None, None) => None,
+            _ => {
+                return Err(s3_error!(
+                    MalformedXML,
+                    "retention needs both a mode and a retain until date"
+                ))
+            }
+        };
+
+        // Retention can only be made stricter, unless it's governance retention and the request
+        // bypasses it.
+        if let Some((mode, until)) = active_retention(&object.metadata, now) {
+            let weakened = new.as_ref().is_none_or(|(new_mode, new_until)| {
+                *new_until < until
+                    || (mode == ObjectLockRetentionMode::COMPLIANCE
+                        && new_mode != ObjectLockRetentionMode::COMPLIANCE)
+            });
+            let bypassed = mode == ObjectLockRetentionMode::GOVERNANCE
+                && input.bypass_governance_retention.unwrap_or_default();
+            if weakened && !bypassed {
+                return Err(s3_error!(
+                    AccessDenied,
+                    "the object's {mode} retention can't be shortened or removed"
+                ));
+            }
+        }
+
+        let mut metadata = object.metadata.clone();
+        match new {
+            Some((mode, until)) => {
+                metadata.insert(RETENTION_MODE_METADATA_KEY.to_string(), mode);
+                metadata.insert(RETAIN_UNTIL_METADATA_KEY.to_string(), until.to_string());
+            }
+            None => {
+                metadata.remove(RETENTION_MODE_METADATA_KEY);
+                metadata.remove(RETAIN_UNTIL_METADATA_KEY);
+            }
+        }
+        let tx_hash = self.rewrite_lock(address, key, object, metadata).await?;
+
+        let mut resp = S3Response::new(PutObjectRetentionOutput::default());
+        set_tx_hash_header(&mut resp, tx_hash);
+
+        action_counter.success = true;
+        Ok(resp)
+    }
+
+    // #[tracing::instrument]
+    async fn put_object_tagging(
+        &self,
+        _req: S3Request<PutObjectTaggingInput>,
+    ) -> S3Result<S3Response<PutObjectTaggingOutput>> {
+        not_implemented("put_object_tagging", "PutObjectTagging")
+    }
+
+    // #[tracing::instrument]
+    async fn upload_part(
+        &self,
+        req: S3Request<UploadPartInput>,
+    ) -> S3Result<S3Response<UploadPartOutput>> {
+        let mut action_counter = self.action_counter("upload_part", &req.input.bucket);
+        if self.is_read_only {
+            return read_only("UploadPart");
+        }
+
         let UploadPartInput {
             body,
+            bucket,
+            key,
             upload_id,
             part_number,
+            content_length,
             ..
         } = req.input;
 
+        let bucket = self.write_bucket_path(&bucket)?;
         let body = body.ok_or_else(|| s3_error!(IncompleteBody))?;
         let upload_id = Uuid::parse_str(&upload_id).map_err(|_| s3_error!(InvalidRequest))?;
+        let upload_path = self.get_upload_path(&upload_id);
+        self.check_part_number(part_number)?;
+
+        let check_manifest = |manifest: Option<UploadManifest>| match manifest {
+            None => Err(s3_error!(NoSuchUpload)),
+            Some(m) if !m.matches(&bucket, &key) => Err(s3_error!(
+                InvalidRequest,
+                "bucket and key don't match the multipart upload"
+            )),
+            Some(m) => Ok(m),
+        };
+        let manifest = check_manifest(UploadManifest::load(&upload_path).await?)?;
+
+        // Parts count against the quota once the upload completes, but one that can't fit is
+        // refused before it's received.
+        if let Some(content_length) = content_length {
+            let uploaded: u64 = manifest
+                .parts
+                .iter()
+                .filter(|(n, _)| **n != part_number)
+                .map(|(_, part)| part.size)
+                .sum();
+            self.check_quota(
+                req.credentials.as_ref(),
+                uploaded + u64::try_from(content_length).unwrap_or_default(),
+            )?;
+        }
 
-        let file_path = self.get_upload_part_path(&upload_id, part_number);
-        let mut md5_hash = <Md5 as Digest>::new();
-        let stream = body.inspect_ok(|bytes| md5_hash.update(bytes.as_ref()));
-        let mut file = try_!(fs::File::create(&file_path).await);
-        let size = copy_bytes(stream, &mut file).await?;
-        try_!(file.flush().await);
-
-        let md5_sum = hex(md5_hash.finalize());
-        debug!(path = ?file_path, ?size, %md5_sum, "write file");
+        let mut e_tag_hash = self.etag_algorithm.hasher();
+        let stream = body.inspect_ok(|bytes| e_tag_hash.update(bytes.as_ref()));
+        let size = if self.multipart_parts_in_backend {
+            let content_length = content_length.ok_or_else(|| s3_error!(MissingContentLength))?;
+            let size = try_!(u64::try_from(content_length));
+            let Some(address) = self.get_bucket_address_by_alias(&bucket).await? else {
+                return Err(s3_error!(NoSuchBucket));
+            };
+            let machine = Bucket::attach(address)
+                .await
+                .map_err(|e| S3Error::new(S3ErrorCode::Custom(ByteString::from(e.to_string()))))?;
+            let part_key = self.get_upload_part_key(&upload_id, part_number);
+            self.store_part(&machine, &part_key, stream, size).await?;
+            debug!(%part_key, ?size, "write part");
+            size
+        } else {
+            let file_path = self.get_upload_part_path(&upload_id, part_number);
+            let mut file = try_!(fs::File::create(&file_path).await);
+            let size = copy_bytes(stream, &mut file).await?;
+            try_!(file.sync_all().await);
+            debug!(path = ?file_path, ?size, "write file");
+            size
+        };
+        let e_tag = e_tag_hash.e_tag();
+
+        // Record the part only once it's durable, so the manifest never points at missing data.
+        {
+            let _lock = self.upload_manifests.lock().await;
+            let mut manifest = check_manifest(UploadManifest::load(&upload_path).await?)?;
+            manifest.parts.insert(
+                part_number,
+                UploadPart {
+                    e_tag: e_tag.clone(),
+                    size,
+                },
+            );
+            manifest.save(&upload_path).await?;
+        }
 
         let output = UploadPartOutput {
-            e_tag: Some(format!("\"{md5_sum}\"")),
+            e_tag: Some(e_tag),
             ..Default::default()
         };
         action_counter.success = true;
@@ -998,7 +2881,142 @@ where
     }
 }
 
+/// Smallest backend key to list from to skip `key`. A key ending in the delimiter is a common
+/// prefix, which is skipped with every key it rolls up.
+fn key_after(mut key: Vec<u8>, delimiter: &str) -> Vec<u8> {
+    if !delimiter.is_empty() && key.ends_with(delimiter.as_bytes()) {
+        if let Some(last) = key.last_mut().filter(|b| **b < u8::MAX) {
+            *last += 1;
+            return key;
+        }
+    }
+    key.push(0);
+    key
+}
+
+/// A condition on the object currently at a key, under which a write may proceed.
+enum WritePrecondition {
+    /// `If-None-Match: *`: the key must not exist yet.
+    Absent,
+    /// `If-Match`: the object must exist and, unless the etag is `*`, have this etag.
+    Matches(String),
+}
+
+/// The precondition set by a write's `If-Match` or `If-None-Match: *` header, if any. Other
+/// `If-None-Match` values only make sense for reads and are ignored.
+fn write_precondition(headers: &http::HeaderMap) -> S3Result<Option<WritePrecondition>> {
+    let create_only = headers
+        .get(IF_NONE_MATCH_HEADER)
+        .is_some_and(|v| v.as_bytes() == b"*");
+    let Some(if_match) = headers.get(IF_MATCH_HEADER) else {
+        return Ok(create_only.then_some(WritePrecondition::Absent));
+    };
+    if create_only {
+        return Err(s3_error!(
+            InvalidRequest,
+            "If-Match and If-None-Match can't be combined"
+        ));
+    }
+    let e_tag = if_match
+        .to_str()
+        .map_err(|_| s3_error!(InvalidArgument, "invalid If-Match header"))?;
+    Ok(Some(WritePrecondition::Matches(e_tag.to_string())))
+}
+
+/// Whether a write asks to override governance retention of the object it replaces. s3s only
+/// parses `x-amz-bypass-governance-retention` for deletes.
+fn bypass_governance(headers: &http::HeaderMap) -> bool {
+    headers
+        .get(BYPASS_GOVERNANCE_HEADER)
+        .is_some_and(|v| v.as_bytes().eq_ignore_ascii_case(b"true"))
+}
+
+/// Compression the `x-recall-compress` header asks a write to be stored with, if any.
+fn storage_compression(headers: &http::HeaderMap) -> S3Result<Option<StorageCompression>> {
+    let Some(value) = headers.get(COMPRESS_HEADER) else {
+        return Ok(None);
+    };
+    let compression = value
+        .to_str()
+        .map_err(|_| s3_error!(InvalidArgument, "invalid {COMPRESS_HEADER} header"))?
+        .parse()
+        .map_err(|e: String| s3_error!(InvalidArgument, "{e}"))?;
+    Ok(Some(compression))
+}
+
+/// Resolves a requested range against an object's size, into the length and `Content-Range` of
+/// the response and the range to download. Unsatisfiable ranges fail with `InvalidRange`.
+fn resolve_range(
+    range: Option<Range>,
+    size: u64,
+) -> S3Result<(u64, Option<String>, Option<String>)> {
+    let Some(range) = range else {
+        return Ok((size, None, None));
+    };
+    let file_range = range.check(size)?;
+    let content_length = file_range.end - file_range.start;
+    let content_range = fmt_content_range(file_range.start, file_range.end - 1, size);
+    let range = format!("{}-{}", file_range.start, file_range.end - 1);
+    Ok((content_length, Some(content_range), Some(range)))
+}
+
 /// <https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/Content-Range>
 fn fmt_content_range(start: u64, end_inclusive: u64, size: u64) -> String {
     format!("bytes {start}-{end_inclusive}/{size}")
 }
+
+/// Keys are byte strings, so they're only listed verbatim when they're valid UTF-8. Clients
+/// asking for `encoding-type=url` get every key back losslessly.
+fn encode_key(key: &[u8], url_encoded: bool) -> S3Result<String> {
+    if url_encoded {
+        return Ok(url::form_urlencoded::byte_serialize(key).collect());
+    }
+
+    String::from_utf8(key.to_vec()).map_err(|_| {
+        s3_error!(
+            InvalidRequest,
+            "key {} is not valid UTF-8, list with encoding-type=url instead",
+            hex(key)
+        )
+    })
+}
+
+/// Continuation tokens are the hex of the backend key to resume from, so keys that aren't
+/// UTF-8 survive the round trip and clients don't see backend keys as such.
+fn decode_continuation_token(token: &str) -> S3Result<Vec<u8>> {
+    hex_simd::decode_to_vec(token)
+        .map_err(|_| s3_error!(InvalidArgument, "the continuation token is not valid"))
+}
+
+/// Tells the client which transaction applied a write.
+fn set_tx_hash_header<T>(resp: &mut S3Response<T>, hash: impl std::fmt::Display) {
+    if let Ok(value) = HeaderValue::try_from(hash.to_string()) {
+        resp.headers.insert(TX_HASH_HEADER, value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::s3::{decode_continuation_token, encode_key};
+    use crate::utils::hex;
+    use s3s::S3ErrorCode;
+
+    #[test]
+    fn test_encode_key() {
+        assert_eq!(encode_key(b"a b", false).unwrap(), "a b");
+        assert_eq!(encode_key(b"a b", true).unwrap(), "a+b");
+
+        assert_eq!(encode_key(b"a\xff", true).unwrap(), "a%FF");
+        let err = encode_key(b"a\xff", false).unwrap_err();
+        assert_eq!(*err.code(), S3ErrorCode::InvalidRequest);
+    }
+
+    #[test]
+    fn test_continuation_token() {
+        let key = b"tenants/a/\xffkey".to_vec();
+        assert_eq!(decode_continuation_token(&hex(&key)).unwrap(), key);
+
+        let err = decode_continuation_token("not hex").unwrap_err();
+        assert_eq!(*err.code(), S3ErrorCode::InvalidArgument);
+    }
+}
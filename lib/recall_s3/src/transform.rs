@@ -0,0 +1,79 @@
+use std::collections::HashMap;
+use std::pin::Pin;
+
+use tokio::io::AsyncRead;
+
+pub type BoxReader = Pin<Box<dyn AsyncRead + Send + Sync>>;
+
+/// What a transform knows about the object being read.
+pub struct TransformContext<'a> {
+    pub bucket: &'a str,
+    pub key: &'a str,
+    pub metadata: &'a HashMap<String, String>,
+}
+
+/// A step applied to object bodies on `get_object`, e.g. decryption or decompression.
+///
+/// Transforms run in registration order over the stored bytes of the requested range.
+pub trait ObjectTransform: Send + Sync {
+    fn transform(&self, ctx: &TransformContext<'_>, reader: BoxReader) -> BoxReader;
+
+    /// Length of the transformed body given the length of its input, or `None` if it can't be
+    /// known up front.
+    fn content_length(&self, _ctx: &TransformContext<'_>, len: u64) -> Option<u64> {
+        Some(len)
+    }
+}
+
+pub fn apply_transforms(
+    transforms: &[Box<dyn ObjectTransform>],
+    ctx: &TransformContext<'_>,
+    reader: BoxReader,
+    len: u64,
+) -> (BoxReader, Option<u64>) {
+    transforms
+        .iter()
+        .fold((reader, Some(len)), |(reader, len), t| {
+            let len = len.and_then(|len| t.content_length(ctx, len));
+            (t.transform(ctx, reader), len)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::transform::{apply_transforms, BoxReader, ObjectTransform, TransformContext};
+    use std::collections::HashMap;
+    use std::io::Cursor;
+    use tokio::io::AsyncReadExt;
+
+    struct Prefix(&'static [u8]);
+
+    impl ObjectTransform for Prefix {
+        fn transform(&self, _: &TransformContext<'_>, reader: BoxReader) -> BoxReader {
+            Box::pin(Cursor::new(self.0).chain(reader))
+        }
+
+        fn content_length(&self, _: &TransformContext<'_>, len: u64) -> Option<u64> {
+            Some(len + self.0.len() as u64)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_apply_transforms() {
+        let metadata = HashMap::new();
+        let ctx = TransformContext {
+            bucket: "foo",
+            key: "bar",
+            metadata: &metadata,
+        };
+        let transforms: Vec<Box<dyn ObjectTransform>> =
+            vec![Box::new(Prefix(b"b")), Box::new(Prefix(b"a"))];
+
+        let (mut reader, len) = apply_transforms(&transforms, &ctx, Box::pin(Cursor::new(b"c")), 1);
+
+        let mut body = Vec::new();
+        reader.read_to_end(&mut body).await.unwrap();
+        assert_eq!(body, b"abc");
+        assert_eq!(len, Some(3));
+    }
+}
@@ -0,0 +1,146 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use s3s::dto::PartNumber;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+use uuid::Uuid;
+
+use crate::bucket::BucketNameWithOwner;
+use crate::error::*;
+
+/// Write-ahead record of a multipart upload: the bucket and key it was created for, and the
+/// parts received so far. It's synced to disk on every change, so uploads survive a restart.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UploadManifest {
+    pub owner: String,
+    pub bucket: String,
+    pub key: String,
+    #[serde(default)]
+    pub parts: BTreeMap<PartNumber, UploadPart>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UploadPart {
+    pub e_tag: String,
+    pub size: u64,
+}
+
+impl UploadManifest {
+    pub fn new(bucket: &BucketNameWithOwner, key: &str) -> Self {
+        Self {
+            owner: bucket.owner().to_string(),
+            bucket: bucket.name(),
+            key: key.to_string(),
+            parts: BTreeMap::new(),
+        }
+    }
+
+    pub fn file_name(upload_id: &Uuid) -> String {
+        format!("upload-{upload_id}.json")
+    }
+
+    pub fn matches(&self, bucket: &BucketNameWithOwner, key: &str) -> bool {
+        self.owner == bucket.owner().to_string() && self.bucket == bucket.name() && self.key == key
+    }
+
+    pub async fn load(path: &Path) -> Result<Option<Self>> {
+        let data = match fs::read(path).await {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Some(serde_json::from_slice(&data)?))
+    }
+
+    /// Replaces the manifest at `path` atomically, syncing it to disk before returning.
+    pub async fn save(&self, path: &Path) -> Result {
+        let tmp_path = path.with_extension("json.tmp");
+        let mut file = fs::File::create(&tmp_path).await?;
+        file.write_all(&serde_json::to_vec(self)?).await?;
+        file.sync_all().await?;
+        fs::rename(&tmp_path, path).await?;
+
+        if let Some(dir) = path.parent() {
+            fs::File::open(dir).await?.sync_all().await?;
+        }
+        Ok(())
+    }
+
+    /// Every upload recorded in `root`, e.g. to resume them after a restart.
+    pub async fn list(root: &Path) -> Result<Vec<(Uuid, Self)>> {
+        let mut uploads = Vec::new();
+        let mut entries = fs::read_dir(root).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let file_name = entry.file_name();
+            let Some(upload_id) = file_name
+                .to_str()
+                .and_then(|name| name.strip_prefix("upload-")?.strip_suffix(".json"))
+                .and_then(|id| Uuid::parse_str(id).ok())
+            else {
+                continue;
+            };
+            if let Some(manifest) = Self::load(&entry.path()).await? {
+                uploads.push((upload_id, manifest));
+            }
+        }
+        Ok(uploads)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bucket::BucketNameWithOwner;
+    use crate::upload::{UploadManifest, UploadPart};
+    use tempfile::tempdir;
+    use uuid::Uuid;
+
+    fn bucket() -> BucketNameWithOwner {
+        BucketNameWithOwner::from(
+            "0xe1209fb9aa2d08c8541297ec06ee6bbb63b10edc",
+            &"foo".to_string(),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_upload_manifest() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("upload.json");
+        assert!(UploadManifest::load(&path).await.unwrap().is_none());
+
+        let bucket = bucket();
+        let manifest = UploadManifest::new(&bucket, "a/b.txt");
+        manifest.save(&path).await.unwrap();
+
+        let loaded = UploadManifest::load(&path).await.unwrap().unwrap();
+        assert_eq!(manifest, loaded);
+        assert!(loaded.matches(&bucket, "a/b.txt"));
+        assert!(!loaded.matches(&bucket, "a/c.txt"));
+    }
+
+    #[tokio::test]
+    async fn test_upload_manifests_survive_restart() {
+        let root = tempdir().unwrap();
+        let bucket = bucket();
+
+        let upload_id = Uuid::new_v4();
+        let path = root.path().join(UploadManifest::file_name(&upload_id));
+        let mut manifest = UploadManifest::new(&bucket, "a/b.txt");
+        manifest.save(&path).await.unwrap();
+        manifest.parts.insert(
+            1,
+            UploadPart {
+                e_tag: "\"abc\"".to_string(),
+                size: 5,
+            },
+        );
+        manifest.save(&path).await.unwrap();
+        std::fs::write(root.path().join("unrelated.json"), "{}").unwrap();
+
+        // A fresh process only has what's on disk.
+        let uploads = UploadManifest::list(root.path()).await.unwrap();
+        assert_eq!(uploads, vec![(upload_id, manifest)]);
+    }
+}
@@ -1,13 +1,15 @@
 use crate::error::*;
+use crate::etag::ETagHasher;
+use std::collections::VecDeque;
 use std::pin::Pin;
 use std::task::{Context, Poll};
 
 use bytes::Bytes;
 use futures::{Stream, StreamExt};
-use md5::{Digest, Md5};
 use s3s::StdError;
 use tokio::io::AsyncWriteExt;
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::task::JoinHandle;
 
 pub async fn copy_bytes<S, W>(mut stream: S, writer: &mut W) -> Result<u64>
 where
@@ -33,19 +35,16 @@ pub fn hex(input: impl AsRef<[u8]>) -> String {
 
 pub struct HashReader<R> {
     inner: R,
-    hasher: Md5,
+    hasher: ETagHasher,
 }
 
 impl<R> HashReader<R> {
-    pub fn new(inner: R) -> Self {
-        Self {
-            inner,
-            hasher: <Md5 as Digest>::new(),
-        }
+    pub fn new(inner: R, hasher: ETagHasher) -> Self {
+        Self { inner, hasher }
     }
 
     pub fn finalize(self) -> Vec<u8> {
-        self.hasher.finalize().to_vec()
+        self.hasher.finalize()
     }
 }
 
@@ -69,16 +68,126 @@ impl<R: AsyncRead + Unpin> AsyncRead for HashReader<R> {
     }
 }
 
+/// A reader that fails instead of ending early when its inner reader ends before `expected`
+/// bytes. A piped download that dies midway then can't pass for a complete, shorter object.
+pub struct ExactLengthReader<R> {
+    inner: R,
+    remaining: u64,
+}
+
+impl<R> ExactLengthReader<R> {
+    pub fn new(inner: R, expected: u64) -> Self {
+        Self {
+            inner,
+            remaining: expected,
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for ExactLengthReader<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let pos = buf.filled().len();
+
+        match Pin::new(&mut self.inner).poll_read(cx, buf) {
+            Poll::Ready(Ok(())) => {
+                let n = (buf.filled().len() - pos) as u64;
+                if n == 0 && self.remaining > 0 {
+                    return Poll::Ready(Err(std::io::Error::new(
+                        std::io::ErrorKind::UnexpectedEof,
+                        format!("stream ended {} bytes short", self.remaining),
+                    )));
+                }
+                self.remaining = self.remaining.saturating_sub(n);
+                Poll::Ready(Ok(()))
+            }
+            other => other,
+        }
+    }
+}
+
+/// A reader over several readers one after the other, e.g. the parts of a multipart upload.
+pub struct Concat<R> {
+    readers: VecDeque<R>,
+}
+
+impl<R> Concat<R> {
+    pub fn new(readers: impl IntoIterator<Item = R>) -> Self {
+        Self {
+            readers: readers.into_iter().collect(),
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for Concat<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let pos = buf.filled().len();
+
+        while let Some(reader) = self.readers.front_mut() {
+            match Pin::new(reader).poll_read(cx, buf) {
+                Poll::Ready(Ok(())) if buf.filled().len() == pos && buf.remaining() > 0 => {
+                    self.readers.pop_front();
+                }
+                other => return other,
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// A stream that aborts the task feeding it once dropped, e.g. when a client disconnects
+/// mid-download.
+pub struct AbortOnDrop<S> {
+    inner: S,
+    handle: JoinHandle<()>,
+}
+
+impl<S> AbortOnDrop<S> {
+    pub fn new(inner: S, handle: JoinHandle<()>) -> Self {
+        Self { inner, handle }
+    }
+}
+
+impl<S: Stream + Unpin> Stream for AbortOnDrop<S> {
+    type Item = S::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.inner).poll_next(cx)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<S> Drop for AbortOnDrop<S> {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::utils::{hex, HashReader};
+    use crate::etag::ETagAlgorithm;
+    use crate::utils::{hex, AbortOnDrop, Concat, ExactLengthReader, HashReader};
+    use futures::StreamExt;
     use std::io::Cursor;
+    use std::time::Duration;
+    use tokio::io::AsyncWriteExt;
+    use tokio_util::io::ReaderStream;
 
     #[tokio::test]
     async fn test_hasher() {
         let data = Cursor::new("The quick brown fox jumps over the lazy dog");
         let reader = tokio::io::BufReader::new(data);
-        let mut hash_reader = HashReader::new(reader);
+        let mut hash_reader = HashReader::new(reader, ETagAlgorithm::Md5.hasher());
         let _ = tokio::io::copy(&mut hash_reader, &mut tokio::io::empty())
             .await
             .unwrap();
@@ -86,4 +195,93 @@ mod tests {
 
         assert_eq!("9e107d9d372bb6826bd81d3542a419d6", hash);
     }
+
+    #[tokio::test]
+    async fn test_exact_length_reader() {
+        let mut complete = ExactLengthReader::new(Cursor::new(b"hello".to_vec()), 5);
+        let mut out = Vec::new();
+        tokio::io::copy(&mut complete, &mut out).await.unwrap();
+        assert_eq!(out, b"hello");
+
+        let mut truncated = ExactLengthReader::new(Cursor::new(b"hel".to_vec()), 5);
+        let err = tokio::io::copy(&mut truncated, &mut tokio::io::sink())
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+
+    #[tokio::test]
+    async fn test_concat() {
+        let parts = ["hello", "", " ", "world"].map(|p| Cursor::new(p.as_bytes().to_vec()));
+        let mut out = Vec::new();
+        tokio::io::copy(&mut Concat::new(parts), &mut out)
+            .await
+            .unwrap();
+        assert_eq!(out, b"hello world");
+
+        // A part that breaks off fails the whole read.
+        let parts = [
+            ExactLengthReader::new(Cursor::new(b"hel".to_vec()), 5),
+            ExactLengthReader::new(Cursor::new(b"world".to_vec()), 5),
+        ];
+        let err = tokio::io::copy(&mut Concat::new(parts), &mut tokio::io::sink())
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_slow_consumer_drains_download() {
+        // The same plumbing as a download: a task writing into a small pipe, read out as the
+        // response body by a consumer that takes its time, all on a single thread.
+        const LEN: usize = 4 * 1024 * 1024;
+        let (mut writer, reader) = tokio::io::duplex(4096);
+        let handle = tokio::spawn(async move {
+            for chunk in vec![7u8; LEN].chunks(64 * 1024) {
+                writer.write_all(chunk).await.unwrap();
+            }
+        });
+
+        let mut stream = AbortOnDrop::new(ReaderStream::new(reader), handle);
+        let received = tokio::time::timeout(Duration::from_secs(30), async {
+            let mut received = 0;
+            while let Some(chunk) = stream.next().await {
+                received += chunk.unwrap().len();
+                if received % (256 * 1024) < 4096 {
+                    tokio::time::sleep(Duration::from_millis(1)).await;
+                }
+            }
+            received
+        })
+        .await
+        .expect("download stalled");
+        assert_eq!(received, LEN);
+    }
+
+    #[tokio::test]
+    async fn test_abort_on_drop() {
+        let (mut writer, reader) = tokio::io::duplex(16);
+        let handle = tokio::spawn(async move {
+            loop {
+                if writer.write_all(&[0; 16]).await.is_err() {
+                    break;
+                }
+            }
+        });
+        let abort_handle = handle.abort_handle();
+
+        let mut stream = AbortOnDrop::new(ReaderStream::new(reader), handle);
+        assert!(stream.next().await.unwrap().is_ok());
+        assert!(!abort_handle.is_finished());
+
+        // Dropping the response body mid-stream stops the download.
+        drop(stream);
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while !abort_handle.is_finished() {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .unwrap();
+    }
 }
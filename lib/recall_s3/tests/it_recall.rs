@@ -6,18 +6,41 @@
 use anyhow::Result;
 use aws_config::SdkConfig;
 use aws_credential_types::provider::SharedCredentialsProvider;
+use aws_sdk_s3::config::interceptors::BeforeDeserializationInterceptorContextRef;
+use aws_sdk_s3::config::ConfigBag;
 use aws_sdk_s3::config::Credentials;
+use aws_sdk_s3::config::Intercept;
 use aws_sdk_s3::config::Region;
+use aws_sdk_s3::config::RuntimeComponents;
+use aws_sdk_s3::error::BoxError;
+use aws_sdk_s3::error::ProvideErrorMetadata;
 use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::primitives::DateTime;
 use aws_sdk_s3::types::error::BucketAlreadyExists;
 use aws_sdk_s3::types::BucketLocationConstraint;
+use aws_sdk_s3::types::ChecksumAlgorithm;
 use aws_sdk_s3::types::CompletedMultipartUpload;
 use aws_sdk_s3::types::CompletedPart;
 use aws_sdk_s3::types::CreateBucketConfiguration;
+use aws_sdk_s3::types::Delete;
+use aws_sdk_s3::types::MetadataDirective;
+use aws_sdk_s3::types::ObjectCannedAcl;
+use aws_sdk_s3::types::ObjectIdentifier;
+use aws_sdk_s3::types::ObjectLockLegalHold;
+use aws_sdk_s3::types::ObjectLockLegalHoldStatus;
+use aws_sdk_s3::types::ObjectLockMode;
+use aws_sdk_s3::types::ObjectLockRetention;
+use aws_sdk_s3::types::ObjectLockRetentionMode;
+use aws_sdk_s3::types::ObjectStorageClass;
+use aws_sdk_s3::types::Payer;
+use aws_sdk_s3::types::Permission;
+use aws_sdk_s3::types::StorageClass;
 use aws_sdk_s3::Client;
 use ethers::utils::hex::ToHexExt;
 use once_cell::sync::Lazy;
 use recall_provider::json_rpc::JsonRpcProvider;
+use recall_s3::clock::MockClock;
+use recall_s3::quota::InMemoryQuotaStore;
 use recall_s3::Recall;
 use recall_sdk::network::Network;
 use recall_signer::key::parse_secret_key;
@@ -26,7 +49,11 @@ use recall_signer::Signer;
 use recall_signer::Wallet;
 use s3s::auth::SimpleAuth;
 use s3s::service::S3ServiceBuilder;
+use s3s::S3ErrorCode;
+use std::collections::HashMap;
 use std::env;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 use tempfile::tempdir;
 use tokio::sync::Mutex;
 use tokio::sync::MutexGuard;
@@ -36,6 +63,16 @@ use tracing::{debug, error};
 
 const DOMAIN_NAME: &str = "localhost:8014";
 
+/// Tenants registered in the test service, as (access key, secret key, key prefix).
+const TENANTS: [(&str, &str, &str); 3] = [
+    ("tenant-a", "tenant-a-secret", "tenants/a/"),
+    ("tenant-b", "tenant-b-secret", "tenants/b/"),
+    ("tenant-quota", "tenant-quota-secret", "tenants/quota/"),
+];
+
+/// Bytes the `tenant-quota` tenant may store.
+const TENANT_QUOTA: u64 = 1000;
+
 struct SdkConfigWithAddress {
     sdk: SdkConfig,
     address: String,
@@ -92,20 +129,31 @@ async fn config() -> &'static SdkConfigWithAddress {
         )
         .unwrap();
 
-        let recall = Recall::new(
+        let mut recall = Recall::new(
             tempdir().unwrap().into_path(),
             provider,
             Some(WALLET.get().unwrap().clone()),
         )
         .unwrap();
+        recall.region = Some("us-west-2".to_string());
+        recall.max_keys_hard_limit = 2000;
+        recall.key_prefixes = TENANTS
+            .iter()
+            .map(|(access_key, _, prefix)| (access_key.to_string(), prefix.to_string()))
+            .collect();
+        recall.quota = Some(Box::new(InMemoryQuotaStore::new(HashMap::from([(
+            TENANTS[2].0.to_string(),
+            TENANT_QUOTA,
+        )]))));
 
         // Setup S3 service
         let service = {
             let mut b = S3ServiceBuilder::new(recall);
-            b.set_auth(SimpleAuth::from_single(
-                cred.access_key_id(),
-                cred.secret_access_key(),
-            ));
+            let mut auth = SimpleAuth::from_single(cred.access_key_id(), cred.secret_access_key());
+            for (access_key, secret_key, _) in TENANTS {
+                auth.register(access_key.to_string(), secret_key.to_string().into());
+            }
+            b.set_auth(auth);
             b.set_base_domain(DOMAIN_NAME);
             b.build()
         };
@@ -156,6 +204,53 @@ async fn delete_object(c: &Client, bucket: &String, key: &str) -> Result<()> {
     Ok(())
 }
 
+/// Records a response header and the response status, which the SDK output types don't expose.
+#[derive(Debug, Clone)]
+struct CaptureHeader {
+    name: &'static str,
+    value: Arc<std::sync::Mutex<Option<String>>>,
+    status: Arc<std::sync::Mutex<Option<u16>>>,
+}
+
+impl CaptureHeader {
+    fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            value: Arc::default(),
+            status: Arc::default(),
+        }
+    }
+
+    fn get(&self) -> Option<String> {
+        self.value.lock().unwrap().clone()
+    }
+
+    fn status(&self) -> Option<u16> {
+        *self.status.lock().unwrap()
+    }
+}
+
+impl Intercept for CaptureHeader {
+    fn name(&self) -> &'static str {
+        "CaptureHeader"
+    }
+
+    fn read_after_transmit(
+        &self,
+        context: &BeforeDeserializationInterceptorContextRef<'_>,
+        _runtime_components: &RuntimeComponents,
+        _cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        *self.value.lock().unwrap() = context
+            .response()
+            .headers()
+            .get(self.name)
+            .map(str::to_string);
+        *self.status.lock().unwrap() = Some(context.response().status().as_u16());
+        Ok(())
+    }
+}
+
 macro_rules! log_and_unwrap {
     ($result:expr) => {
         match $result {
@@ -195,6 +290,50 @@ async fn test_bucket_exists() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+#[tracing::instrument]
+async fn test_create_bucket_subcommand() -> Result<()> {
+    let _guard = serial().await;
+
+    let config = config().await;
+    let c = Client::new(&config.sdk);
+
+    // The create-bucket subcommand goes to Recall directly, without an S3 request.
+    let recall = test_recall(Some(get_wallet().await))?;
+    let bucket = "test-create-subcommand".to_string();
+    recall
+        .create_bucket(&recall.new_bucket_path(&bucket)?)
+        .await?;
+
+    // The bucket is listed by its alias, with the creation date written along with it.
+    let listed = c.list_buckets().send().await?;
+    let created = listed
+        .buckets()
+        .iter()
+        .find(|b| b.name() == Some(bucket.as_str()))
+        .expect("created bucket should be listed");
+    assert!(created.creation_date().is_some());
+
+    // The alias is taken now, whichever way a second bucket is created.
+    let err = recall
+        .create_bucket(&recall.new_bucket_path(&bucket)?)
+        .await
+        .unwrap_err();
+    assert_eq!(*err.code(), S3ErrorCode::BucketAlreadyExists);
+    assert!(create_bucket(&c, &bucket)
+        .await
+        .err()
+        .unwrap()
+        .source()
+        .unwrap()
+        .source()
+        .unwrap()
+        .downcast_ref::<BucketAlreadyExists>()
+        .is_some());
+
+    Ok(())
+}
+
 #[tokio::test]
 #[tracing::instrument]
 async fn test_list_buckets() -> Result<()> {
@@ -479,3 +618,2764 @@ async fn test_copy() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_delete_bucket_not_empty() -> Result<()> {
+    let _guard = serial().await;
+
+    let config = config().await;
+    let c = Client::new(&config.sdk);
+
+    let bucket = "test-delete-bucket";
+    let bucket_with_owner = format!("{}.{}", &config.address, bucket);
+
+    create_bucket(&c, bucket).await?;
+
+    let keys = ["a.txt", "b.txt", "c/d.txt"];
+    for key in keys {
+        c.put_object()
+            .bucket(&bucket_with_owner)
+            .key(key)
+            .body(ByteStream::from_static(b"hello world\n"))
+            .send()
+            .await?;
+    }
+
+    // wait for object resolution
+    sleep(Duration::from_millis(5000)).await;
+
+    {
+        let err = c
+            .delete_bucket()
+            .bucket(&bucket_with_owner)
+            .send()
+            .await
+            .unwrap_err();
+        assert_eq!(err.into_service_error().code(), Some("BucketNotEmpty"));
+    }
+
+    {
+        c.delete_bucket()
+            .bucket(&bucket_with_owner)
+            .customize()
+            .mutate_request(|req| {
+                req.headers_mut().insert("x-recall-force", "true");
+            })
+            .send()
+            .await?;
+
+        let ans = c
+            .list_objects_v2()
+            .bucket(&bucket_with_owner)
+            .send()
+            .await?;
+        assert!(ans.contents().is_empty());
+    }
+
+    // Once empty, the bucket can be deleted without forcing.
+    c.delete_bucket().bucket(&bucket_with_owner).send().await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_copy_rename_large_object() -> Result<()> {
+    let _guard = serial().await;
+
+    let config = config().await;
+    let c = Client::new(&config.sdk);
+
+    let bucket = "test-copy-rename";
+    let bucket_with_owner = format!("{}.{}", &config.address, bucket);
+
+    let key = "large.bin";
+    let renamed_key = "large-renamed.bin";
+    let content: Vec<u8> = (0..1024 * 1024).map(|i| (i % 251) as u8).collect();
+
+    create_bucket(&c, bucket).await?;
+
+    c.put_object()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .body(ByteStream::from(content.clone()))
+        .send()
+        .await?;
+
+    // wait for object resolution
+    sleep(Duration::from_millis(5000)).await;
+
+    c.copy_object()
+        .bucket(&bucket_with_owner)
+        .key(renamed_key)
+        .copy_source(format!("{}/{}", &bucket_with_owner, &key))
+        .send()
+        .await?;
+
+    // wait for object resolution
+    sleep(Duration::from_millis(5000)).await;
+
+    {
+        let ans = c
+            .get_object()
+            .bucket(&bucket_with_owner)
+            .key(renamed_key)
+            .send()
+            .await?;
+
+        let body = ans.body.collect().await?.into_bytes();
+        assert_eq!(body.as_ref(), content.as_slice());
+    }
+
+    {
+        delete_object(&c, &bucket_with_owner, key).await?;
+        delete_object(&c, &bucket_with_owner, renamed_key).await?;
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_put_object_if_none_match() -> Result<()> {
+    let _guard = serial().await;
+
+    let config = config().await;
+    let c = Client::new(&config.sdk);
+
+    let bucket = "test-create-only";
+    let bucket_with_owner = format!("{}.{}", &config.address, bucket);
+    let key = "lock";
+
+    create_bucket(&c, bucket).await?;
+
+    let put = || {
+        c.put_object()
+            .bucket(&bucket_with_owner)
+            .key(key)
+            .body(ByteStream::from_static(b"owner"))
+            .customize()
+            .mutate_request(|req| {
+                req.headers_mut().insert("if-none-match", "*");
+            })
+            .send()
+    };
+
+    let (first, second) = tokio::join!(put(), put());
+    assert_eq!(
+        [first.is_ok(), second.is_ok()]
+            .iter()
+            .filter(|ok| **ok)
+            .count(),
+        1
+    );
+
+    // wait for object resolution
+    sleep(Duration::from_millis(5000)).await;
+
+    {
+        let err = put().await.unwrap_err();
+        assert_eq!(err.into_service_error().code(), Some("PreconditionFailed"));
+    }
+
+    {
+        delete_object(&c, &bucket_with_owner, key).await?;
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_list_objects_v2_page_boundary() -> Result<()> {
+    let _guard = serial().await;
+
+    let config = config().await;
+    let c = Client::new(&config.sdk);
+
+    let bucket = "test-list-boundary";
+    let bucket_with_owner = format!("{}.{}", &config.address, bucket);
+
+    create_bucket(&c, bucket).await?;
+
+    let keys = ["boundary/a.txt", "boundary/b.txt", "boundary/c.txt"];
+    for key in keys {
+        c.put_object()
+            .bucket(&bucket_with_owner)
+            .key(key)
+            .body(ByteStream::from_static(b"hello world\n"))
+            .send()
+            .await?;
+    }
+
+    // wait for object resolution
+    sleep(Duration::from_millis(5000)).await;
+
+    {
+        // exactly one full page
+        let ans = c
+            .list_objects_v2()
+            .bucket(&bucket_with_owner)
+            .prefix("boundary/")
+            .max_keys(3)
+            .send()
+            .await?;
+        assert_eq!(ans.contents().len(), 3);
+        assert_eq!(ans.is_truncated(), Some(false));
+        assert!(ans.next_continuation_token().is_none());
+    }
+
+    {
+        // a full page followed by a last, partial one
+        let first = c
+            .list_objects_v2()
+            .bucket(&bucket_with_owner)
+            .prefix("boundary/")
+            .max_keys(2)
+            .send()
+            .await?;
+        assert_eq!(first.contents().len(), 2);
+        assert_eq!(first.is_truncated(), Some(true));
+
+        let last = c
+            .list_objects_v2()
+            .bucket(&bucket_with_owner)
+            .prefix("boundary/")
+            .max_keys(2)
+            .continuation_token(first.next_continuation_token().unwrap())
+            .send()
+            .await?;
+        assert_eq!(last.contents().len(), 1);
+        assert_eq!(last.is_truncated(), Some(false));
+    }
+
+    for key in keys {
+        delete_object(&c, &bucket_with_owner, key).await?;
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_create_bucket_location_mismatch() -> Result<()> {
+    let config = config().await;
+    let c = Client::new(&config.sdk);
+
+    let cfg = CreateBucketConfiguration::builder()
+        .location_constraint(BucketLocationConstraint::EuWest1)
+        .build();
+
+    let err = c
+        .create_bucket()
+        .create_bucket_configuration(cfg)
+        .bucket("test-location-mismatch")
+        .send()
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.into_service_error().code(),
+        Some("InvalidLocationConstraint")
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_multipart_key_mismatch() -> Result<()> {
+    let config = config().await;
+    let c = Client::new(&config.sdk);
+
+    let bucket = "test-multipart-mismatch";
+    let bucket_with_owner = format!("{}.{}", &config.address, bucket);
+
+    create_bucket(&c, bucket).await?;
+
+    let key = "sample-test.txt";
+    let upload_id = c
+        .create_multipart_upload()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .send()
+        .await?
+        .upload_id
+        .unwrap();
+
+    let ans = c
+        .upload_part()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .upload_id(&upload_id)
+        .body(ByteStream::from_static(b"hello world\n"))
+        .part_number(1)
+        .send()
+        .await?;
+
+    let upload = CompletedMultipartUpload::builder()
+        .parts(
+            CompletedPart::builder()
+                .e_tag(ans.e_tag.unwrap_or_default())
+                .part_number(1)
+                .build(),
+        )
+        .build();
+
+    let err = c
+        .complete_multipart_upload()
+        .bucket(&bucket_with_owner)
+        .key("another-key.txt")
+        .multipart_upload(upload)
+        .upload_id(&upload_id)
+        .send()
+        .await
+        .unwrap_err();
+    assert_eq!(err.into_service_error().code(), Some("InvalidRequest"));
+
+    c.abort_multipart_upload()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .upload_id(&upload_id)
+        .send()
+        .await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_head_bucket_region() -> Result<()> {
+    let config = config().await;
+    let c = Client::new(&config.sdk);
+
+    let bucket = "test-head-bucket";
+    let bucket_with_owner = format!("{}.{}", &config.address, bucket);
+
+    create_bucket(&c, bucket).await?;
+
+    let ans = c.head_bucket().bucket(&bucket_with_owner).send().await?;
+    assert_eq!(ans.bucket_region(), Some("us-west-2"));
+    let ans = c
+        .get_bucket_location()
+        .bucket(&bucket_with_owner)
+        .send()
+        .await?;
+    assert_eq!(
+        ans.location_constraint()
+            .map(BucketLocationConstraint::as_str),
+        Some("us-west-2")
+    );
+
+    let no_such_bucket = format!("{}.{}", &config.address, "test-no-such-bucket");
+    let err = c
+        .head_bucket()
+        .bucket(&no_such_bucket)
+        .send()
+        .await
+        .unwrap_err();
+    assert!(err.into_service_error().is_not_found());
+    let err = c
+        .get_bucket_location()
+        .bucket(&no_such_bucket)
+        .send()
+        .await
+        .unwrap_err();
+    assert_eq!(err.into_service_error().code(), Some("NoSuchBucket"));
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_list_buckets_prefix() -> Result<()> {
+    let config = config().await;
+    let c = Client::new(&config.sdk);
+
+    let buckets = ["test-lbp-1", "test-lbp-2", "test-lbp-3"];
+    for bucket in buckets {
+        create_bucket(&c, bucket).await?;
+    }
+
+    let list = |query: &'static str| {
+        c.list_buckets()
+            .customize()
+            .mutate_request(move |req| {
+                let uri = format!("{}?{}", req.uri(), query);
+                req.set_uri(uri).unwrap();
+            })
+            .send()
+    };
+
+    {
+        let ans = list("prefix=test-lbp-").await?;
+        let names: Vec<_> = ans.buckets().iter().filter_map(|b| b.name()).collect();
+        assert_eq!(names, buckets);
+    }
+
+    {
+        let ans = list("prefix=test-lbp-&max-buckets=2").await?;
+        let names: Vec<_> = ans.buckets().iter().filter_map(|b| b.name()).collect();
+        assert_eq!(names, &buckets[..2]);
+
+        let ans = list("prefix=test-lbp-&max-buckets=2&continuation-token=test-lbp-2").await?;
+        let names: Vec<_> = ans.buckets().iter().filter_map(|b| b.name()).collect();
+        assert_eq!(names, &buckets[2..]);
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_put_object_short_body() -> Result<()> {
+    let config = config().await;
+    let c = Client::new(&config.sdk);
+
+    let bucket = "test-short-body";
+    let bucket_with_owner = format!("{}.{}", &config.address, bucket);
+
+    create_bucket(&c, bucket).await?;
+
+    let err = c
+        .put_object()
+        .bucket(&bucket_with_owner)
+        .key("short.txt")
+        .body(ByteStream::from_static(b"hello world\n"))
+        .customize()
+        .mutate_request(|req| {
+            req.headers_mut().insert("content-length", "100");
+        })
+        .send()
+        .await
+        .unwrap_err();
+    assert_eq!(err.into_service_error().code(), Some("IncompleteBody"));
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_put_object_tx_hash() -> Result<()> {
+    let config = config().await;
+    let c = Client::new(&config.sdk);
+
+    let bucket = "test-tx-hash";
+    let bucket_with_owner = format!("{}.{}", &config.address, bucket);
+    let key = "sample.txt";
+
+    create_bucket(&c, bucket).await?;
+
+    let tx_hash = CaptureHeader::new("x-recall-tx-hash");
+    c.put_object()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .body(ByteStream::from_static(b"hello world\n"))
+        .customize()
+        .interceptor(tx_hash.clone())
+        .send()
+        .await?;
+    assert!(tx_hash.get().is_some_and(|hash| !hash.is_empty()));
+
+    delete_object(&c, &bucket_with_owner, key).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_put_object_reserved_metadata() -> Result<()> {
+    let config = config().await;
+    let c = Client::new(&config.sdk);
+
+    let bucket = "test-reserved-metadata";
+    let bucket_with_owner = format!("{}.{}", &config.address, bucket);
+    let key = "sample.txt";
+
+    create_bucket(&c, bucket).await?;
+
+    let err = c
+        .put_object()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .metadata("etag", "\"forged\"")
+        .body(ByteStream::from_static(b"hello world\n"))
+        .send()
+        .await
+        .unwrap_err();
+    assert_eq!(err.into_service_error().code(), Some("InvalidArgument"));
+
+    c.put_object()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .metadata("author", "me")
+        .body(ByteStream::from_static(b"hello world\n"))
+        .send()
+        .await?;
+
+    let head = c
+        .head_object()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .send()
+        .await?;
+    let metadata = head.metadata().unwrap();
+    assert_eq!(metadata.get("author").map(String::as_str), Some("me"));
+    assert!(!metadata.contains_key("etag"));
+
+    delete_object(&c, &bucket_with_owner, key).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_list_objects_v2_raised_limit() -> Result<()> {
+    let _guard = serial().await;
+
+    let config = config().await;
+    let c = Client::new(&config.sdk);
+
+    let bucket = "test-list-raised-limit";
+    let bucket_with_owner = format!("{}.{}", &config.address, bucket);
+
+    create_bucket(&c, bucket).await?;
+
+    let keys: Vec<String> = (0..1001).map(|i| format!("many/{i:04}.txt")).collect();
+    for key in &keys {
+        c.put_object()
+            .bucket(&bucket_with_owner)
+            .key(key)
+            .body(ByteStream::from_static(b"hello world\n"))
+            .send()
+            .await?;
+    }
+
+    // wait for object resolution
+    sleep(Duration::from_millis(5000)).await;
+
+    let ans = c
+        .list_objects_v2()
+        .bucket(&bucket_with_owner)
+        .prefix("many/")
+        .max_keys(1500)
+        .send()
+        .await?;
+    assert_eq!(ans.contents().len(), 1001);
+    assert_eq!(ans.max_keys(), Some(1500));
+    assert_eq!(ans.is_truncated(), Some(false));
+
+    for key in &keys {
+        delete_object(&c, &bucket_with_owner, key).await?;
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_object_acl() -> Result<()> {
+    let config = config().await;
+    let c = Client::new(&config.sdk);
+
+    let bucket = "test-object-acl";
+    let bucket_with_owner = format!("{}.{}", &config.address, bucket);
+    let key = "sample.txt";
+
+    create_bucket(&c, bucket).await?;
+
+    c.put_object()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .body(ByteStream::from_static(b"hello world\n"))
+        .send()
+        .await?;
+
+    // wait for object resolution
+    sleep(Duration::from_millis(5000)).await;
+
+    let acl = c
+        .get_object_acl()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .send()
+        .await?;
+    let grants = acl.grants();
+    assert_eq!(grants.len(), 1);
+    assert_eq!(grants[0].permission(), Some(&Permission::FullControl));
+    assert_eq!(
+        grants[0].grantee().and_then(|g| g.id()),
+        acl.owner().and_then(|o| o.id())
+    );
+
+    c.put_object_acl()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .acl(ObjectCannedAcl::Private)
+        .send()
+        .await?;
+
+    let err = c
+        .put_object_acl()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .acl(ObjectCannedAcl::PublicRead)
+        .send()
+        .await
+        .unwrap_err();
+    assert_eq!(err.into_service_error().code(), Some("NotImplemented"));
+
+    let err = c
+        .get_object_acl()
+        .bucket(&bucket_with_owner)
+        .key("missing.txt")
+        .send()
+        .await
+        .unwrap_err();
+    assert_eq!(err.into_service_error().code(), Some("NoSuchKey"));
+
+    delete_object(&c, &bucket_with_owner, key).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_unsupported_operation() -> Result<()> {
+    let config = config().await;
+    let c = Client::new(&config.sdk);
+
+    let bucket = "test-unsupported";
+    let bucket_with_owner = format!("{}.{}", &config.address, bucket);
+
+    create_bucket(&c, bucket).await?;
+
+    let err = c
+        .get_bucket_policy()
+        .bucket(&bucket_with_owner)
+        .send()
+        .await
+        .unwrap_err();
+    let err = err.into_service_error();
+    assert_eq!(err.code(), Some("NotImplemented"));
+    assert_eq!(err.message(), Some("GetBucketPolicy is not implemented"));
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_unconfigured_bucket_settings() -> Result<()> {
+    let config = config().await;
+    let c = Client::new(&config.sdk);
+
+    let bucket = "test-unconfigured";
+    let bucket_with_owner = format!("{}.{}", &config.address, bucket);
+
+    create_bucket(&c, bucket).await?;
+
+    // Answered like a bucket that never configured them, not with NotImplemented.
+    let accelerate = c
+        .get_bucket_accelerate_configuration()
+        .bucket(&bucket_with_owner)
+        .send()
+        .await?;
+    assert!(accelerate.status().is_none());
+    let payment = c
+        .get_bucket_request_payment()
+        .bucket(&bucket_with_owner)
+        .send()
+        .await?;
+    assert_eq!(payment.payer(), Some(&Payer::BucketOwner));
+    let logging = c
+        .get_bucket_logging()
+        .bucket(&bucket_with_owner)
+        .send()
+        .await?;
+    assert!(logging.logging_enabled().is_none());
+
+    let err = c
+        .get_bucket_website()
+        .bucket(&bucket_with_owner)
+        .send()
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.into_service_error().code(),
+        Some("NoSuchWebsiteConfiguration")
+    );
+    let err = c
+        .get_bucket_replication()
+        .bucket(&bucket_with_owner)
+        .send()
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.into_service_error().code(),
+        Some("ReplicationConfigurationNotFoundError")
+    );
+    let err = c
+        .get_object_lock_configuration()
+        .bucket(&bucket_with_owner)
+        .send()
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.into_service_error().code(),
+        Some("ObjectLockConfigurationNotFoundError")
+    );
+
+    // The bucket still has to exist.
+    let err = c
+        .get_bucket_accelerate_configuration()
+        .bucket(format!(
+            "{}.{}",
+            &config.address, "test-unconfigured-missing"
+        ))
+        .send()
+        .await
+        .unwrap_err();
+    assert_eq!(err.into_service_error().code(), Some("NoSuchBucket"));
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_delete_objects_dry_run() -> Result<()> {
+    let config = config().await;
+    let c = Client::new(&config.sdk);
+
+    let bucket = "test-delete-dry-run";
+    let bucket_with_owner = format!("{}.{}", &config.address, bucket);
+    let key = "sample.txt";
+
+    create_bucket(&c, bucket).await?;
+
+    c.put_object()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .body(ByteStream::from_static(b"hello world\n"))
+        .send()
+        .await?;
+
+    // wait for object resolution
+    sleep(Duration::from_millis(5000)).await;
+
+    let delete = Delete::builder()
+        .objects(ObjectIdentifier::builder().key(key).build()?)
+        .objects(ObjectIdentifier::builder().key("missing.txt").build()?)
+        .build()?;
+    let ans = c
+        .delete_objects()
+        .bucket(&bucket_with_owner)
+        .delete(delete)
+        .customize()
+        .mutate_request(|req| {
+            req.headers_mut().insert("x-recall-dry-run", "true");
+        })
+        .send()
+        .await?;
+
+    let deleted: Vec<_> = ans.deleted().iter().filter_map(|d| d.key()).collect();
+    assert_eq!(deleted, [key]);
+    let missing: Vec<_> = ans.errors().iter().filter_map(|e| e.key()).collect();
+    assert_eq!(missing, ["missing.txt"]);
+    assert_eq!(ans.errors()[0].code(), Some("NoSuchKey"));
+
+    // nothing was actually deleted
+    c.head_object()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .send()
+        .await?;
+
+    delete_object(&c, &bucket_with_owner, key).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_put_object_last_modified() -> Result<()> {
+    let _guard = serial().await;
+
+    let config = config().await;
+    let c = Client::new(&config.sdk);
+
+    let bucket = "test-last-modified";
+    let bucket_with_owner = format!("{}.{}", &config.address, bucket);
+    let key = "sample.txt";
+
+    create_bucket(&c, bucket).await?;
+
+    // A gateway of its own, so no other test's timestamps come from the frozen clock.
+    let mut recall = test_recall(Some(get_wallet().await))?;
+    recall.clock = Box::new(MockClock::new(
+        UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+    ));
+    standalone_client(recall)
+        .await
+        .put_object()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .body(ByteStream::from_static(b"hello world\n"))
+        .send()
+        .await?;
+
+    // wait for object resolution
+    sleep(Duration::from_millis(5000)).await;
+
+    let head = c
+        .head_object()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .send()
+        .await?;
+    assert_eq!(head.last_modified().map(|t| t.secs()), Some(1_700_000_000));
+
+    delete_object(&c, &bucket_with_owner, key).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_bucket_metadata_tags() -> Result<()> {
+    let config = config().await;
+    let c = Client::new(&config.sdk);
+
+    let bucket = "test-bucket-metadata";
+    let bucket_with_owner = format!("{}.{}", &config.address, bucket);
+
+    create_bucket(&c, bucket).await?;
+
+    let ans = c
+        .get_bucket_tagging()
+        .bucket(&bucket_with_owner)
+        .send()
+        .await?;
+    let tag = |key: &str| {
+        ans.tag_set()
+            .iter()
+            .find(|t| t.key() == key)
+            .map(|t| t.value().to_string())
+    };
+    assert_eq!(tag("alias").as_deref(), Some(bucket));
+    assert!(tag("creation_date").is_some());
+    assert!(tag("owner").is_some());
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_put_object_aws_chunked() -> Result<()> {
+    let config = config().await;
+    let c = Client::new(&config.sdk);
+
+    let bucket = "test-aws-chunked";
+    let bucket_with_owner = format!("{}.{}", &config.address, bucket);
+    let content = "hello world\n".repeat(1000);
+
+    create_bucket(&c, bucket).await?;
+
+    // A flexible checksum makes the SDK send the body as `aws-chunked` with a trailer, like
+    // the CLI does. The framing must be stripped before the object is stored.
+    let key = "chunked.txt";
+    c.put_object()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .checksum_algorithm(ChecksumAlgorithm::Crc32)
+        .body(ByteStream::from(content.clone().into_bytes()))
+        .send()
+        .await?;
+
+    let multipart_key = "chunked-multipart.txt";
+    let upload = c
+        .create_multipart_upload()
+        .bucket(&bucket_with_owner)
+        .key(multipart_key)
+        .send()
+        .await?;
+    let upload_id = upload.upload_id().unwrap();
+    let part = c
+        .upload_part()
+        .bucket(&bucket_with_owner)
+        .key(multipart_key)
+        .upload_id(upload_id)
+        .part_number(1)
+        .checksum_algorithm(ChecksumAlgorithm::Crc32)
+        .body(ByteStream::from(content.clone().into_bytes()))
+        .send()
+        .await?;
+    c.complete_multipart_upload()
+        .bucket(&bucket_with_owner)
+        .key(multipart_key)
+        .upload_id(upload_id)
+        .multipart_upload(
+            CompletedMultipartUpload::builder()
+                .parts(
+                    CompletedPart::builder()
+                        .e_tag(part.e_tag().unwrap())
+                        .part_number(1)
+                        .build(),
+                )
+                .build(),
+        )
+        .send()
+        .await?;
+
+    // wait for object resolution
+    sleep(Duration::from_millis(5000)).await;
+
+    for key in [key, multipart_key] {
+        let ans = c
+            .get_object()
+            .bucket(&bucket_with_owner)
+            .key(key)
+            .send()
+            .await?;
+        assert_eq!(ans.content_length(), Some(content.len() as i64));
+        let body = ans.body.collect().await?.into_bytes();
+        assert_eq!(body.as_ref(), content.as_bytes());
+
+        delete_object(&c, &bucket_with_owner, key).await?;
+    }
+
+    Ok(())
+}
+
+fn tenant_client(config: &SdkConfigWithAddress, access_key: &str, secret_key: &str) -> Client {
+    let sdk = config
+        .sdk
+        .to_builder()
+        .credentials_provider(SharedCredentialsProvider::new(Credentials::new(
+            access_key, secret_key, None, None, "tenant",
+        )))
+        .build();
+    Client::new(&sdk)
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_tenant_key_prefixes() -> Result<()> {
+    let _guard = serial().await;
+    let config = config().await;
+    let c = Client::new(&config.sdk);
+    let [(a_access, a_secret, a_prefix), (b_access, b_secret, _), _] = TENANTS;
+    let a = tenant_client(config, a_access, a_secret);
+    let b = tenant_client(config, b_access, b_secret);
+
+    let bucket = "test-tenants";
+    let bucket_with_owner = format!("{}.{}", &config.address, bucket);
+    create_bucket(&c, bucket).await?;
+
+    let key = "shared-name.txt";
+    a.put_object()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .body(ByteStream::from_static(b"from tenant a"))
+        .send()
+        .await?;
+
+    // The same key is a different object for another tenant.
+    let err = b
+        .get_object()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .send()
+        .await
+        .unwrap_err();
+    assert_eq!(err.into_service_error().code(), Some("NoSuchKey"));
+
+    // The tenant lists its keys without the prefix; the admin sees where they're stored.
+    let listed = a
+        .list_objects_v2()
+        .bucket(&bucket_with_owner)
+        .send()
+        .await?;
+    let keys: Vec<_> = listed.contents().iter().filter_map(|o| o.key()).collect();
+    assert_eq!(keys, [key]);
+    let listed = b
+        .list_objects_v2()
+        .bucket(&bucket_with_owner)
+        .send()
+        .await?;
+    assert!(listed.contents().is_empty());
+
+    // Stats only count the tenant's own objects too.
+    for (tenant, objects) in [(&a, "1"), (&b, "0")] {
+        let count = CaptureHeader::new("x-recall-object-count");
+        tenant
+            .head_bucket()
+            .bucket(&bucket_with_owner)
+            .customize()
+            .mutate_request(|req| {
+                req.headers_mut().insert("x-recall-stats", "true");
+            })
+            .interceptor(count.clone())
+            .send()
+            .await?;
+        assert_eq!(count.get().as_deref(), Some(objects));
+    }
+
+    let stored_key = format!("{a_prefix}{key}");
+    c.head_object()
+        .bucket(&bucket_with_owner)
+        .key(&stored_key)
+        .send()
+        .await?;
+
+    let err = a
+        .delete_bucket()
+        .bucket(&bucket_with_owner)
+        .send()
+        .await
+        .unwrap_err();
+    assert_eq!(err.into_service_error().code(), Some("AccessDenied"));
+
+    delete_object(&c, &bucket_with_owner, &stored_key).await?;
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_copy_large_object() -> Result<()> {
+    let _guard = serial().await;
+    let config = config().await;
+    let c = Client::new(&config.sdk);
+
+    let bucket = "test-copy-large";
+    let bucket_with_owner = format!("{}.{}", &config.address, bucket);
+    create_bucket(&c, bucket).await?;
+
+    // Several times the copy buffer, with no repeating pattern a truncation could hide in.
+    let content: Vec<u8> = (0..5 * 1024 * 1024u32)
+        .map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8)
+        .collect();
+    let key = "large.bin";
+    let copy_key = "large-copy.bin";
+    c.put_object()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .body(ByteStream::from(content.clone()))
+        .send()
+        .await?;
+
+    // wait for object resolution
+    sleep(Duration::from_millis(5000)).await;
+
+    c.copy_object()
+        .bucket(&bucket_with_owner)
+        .key(copy_key)
+        .copy_source(format!("{}/{}", &bucket_with_owner, key))
+        .send()
+        .await?;
+
+    // wait for object resolution
+    sleep(Duration::from_millis(5000)).await;
+
+    let object = c
+        .get_object()
+        .bucket(&bucket_with_owner)
+        .key(copy_key)
+        .send()
+        .await?;
+    let body = object.body.collect().await?.into_bytes();
+    assert_eq!(body.len(), content.len());
+    assert!(body.as_ref() == content.as_slice());
+
+    for key in [key, copy_key] {
+        delete_object(&c, &bucket_with_owner, key).await?;
+    }
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_copy_object_keeps_etag() -> Result<()> {
+    let _guard = serial().await;
+    let config = config().await;
+    let c = Client::new(&config.sdk);
+
+    let bucket = "test-copy-etag";
+    let bucket_with_owner = format!("{}.{}", &config.address, bucket);
+    create_bucket(&c, bucket).await?;
+
+    let key = "source.txt";
+    let copy_key = "copy.txt";
+    let put = c
+        .put_object()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .body(ByteStream::from_static(b"same bytes, same etag"))
+        .send()
+        .await?;
+    let e_tag = put.e_tag().unwrap();
+
+    // wait for object resolution
+    sleep(Duration::from_millis(5000)).await;
+
+    let copy = c
+        .copy_object()
+        .bucket(&bucket_with_owner)
+        .key(copy_key)
+        .copy_source(format!("{}/{}", &bucket_with_owner, key))
+        .send()
+        .await?;
+    assert_eq!(copy.copy_object_result().unwrap().e_tag(), Some(e_tag));
+
+    // wait for object resolution
+    sleep(Duration::from_millis(5000)).await;
+
+    let head = c
+        .head_object()
+        .bucket(&bucket_with_owner)
+        .key(copy_key)
+        .send()
+        .await?;
+    assert_eq!(head.e_tag(), Some(e_tag));
+
+    for key in [key, copy_key] {
+        delete_object(&c, &bucket_with_owner, key).await?;
+    }
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_copy_object_to_itself_replaces_metadata() -> Result<()> {
+    let _guard = serial().await;
+    let config = config().await;
+    let c = Client::new(&config.sdk);
+
+    let bucket = "test-copy-self";
+    let bucket_with_owner = format!("{}.{}", &config.address, bucket);
+    create_bucket(&c, bucket).await?;
+
+    let key = "report.json";
+    let content = "{\"ok\":true}";
+    c.put_object()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .content_type("text/plain")
+        .metadata("author", "me")
+        .body(ByteStream::from_static(content.as_bytes()))
+        .send()
+        .await?;
+
+    // wait for object resolution
+    sleep(Duration::from_millis(5000)).await;
+
+    // Like S3, a self-copy that changes nothing is refused.
+    let err = c
+        .copy_object()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .copy_source(format!("{}/{}", &bucket_with_owner, key))
+        .send()
+        .await
+        .unwrap_err();
+    assert_eq!(err.into_service_error().code(), Some("InvalidRequest"));
+
+    c.copy_object()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .copy_source(format!("{}/{}", &bucket_with_owner, key))
+        .metadata_directive(MetadataDirective::Replace)
+        .content_type("application/json")
+        .metadata("reviewed", "yes")
+        .send()
+        .await?;
+
+    // wait for object resolution
+    sleep(Duration::from_millis(5000)).await;
+
+    let head = c
+        .head_object()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .send()
+        .await?;
+    assert_eq!(head.content_type(), Some("application/json"));
+    let metadata = head.metadata().unwrap();
+    assert_eq!(metadata.get("reviewed").map(String::as_str), Some("yes"));
+    assert!(!metadata.contains_key("author"));
+
+    let object = c
+        .get_object()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .send()
+        .await?;
+    let body = object.body.collect().await?.into_bytes();
+    assert_eq!(body.as_ref(), content.as_bytes());
+
+    delete_object(&c, &bucket_with_owner, key).await?;
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_copy_object_metadata_directive() -> Result<()> {
+    let _guard = serial().await;
+    let config = config().await;
+    let c = Client::new(&config.sdk);
+
+    let bucket = "test-copy-directive";
+    let bucket_with_owner = format!("{}.{}", &config.address, bucket);
+    create_bucket(&c, bucket).await?;
+
+    let key = "source.txt";
+    c.put_object()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .content_type("text/plain")
+        .metadata("author", "me")
+        .body(ByteStream::from_static(b"hello world\n"))
+        .send()
+        .await?;
+
+    // wait for object resolution
+    sleep(Duration::from_millis(5000)).await;
+
+    // COPY is the default directive: metadata given with the request is ignored.
+    c.copy_object()
+        .bucket(&bucket_with_owner)
+        .key("copied.txt")
+        .copy_source(format!("{}/{}", &bucket_with_owner, key))
+        .metadata_directive(MetadataDirective::Copy)
+        .content_type("application/json")
+        .metadata("reviewed", "yes")
+        .send()
+        .await?;
+    c.copy_object()
+        .bucket(&bucket_with_owner)
+        .key("replaced.txt")
+        .copy_source(format!("{}/{}", &bucket_with_owner, key))
+        .metadata_directive(MetadataDirective::Replace)
+        .content_type("application/json")
+        .metadata("reviewed", "yes")
+        .send()
+        .await?;
+
+    // wait for object resolution
+    sleep(Duration::from_millis(5000)).await;
+
+    let copied = c
+        .head_object()
+        .bucket(&bucket_with_owner)
+        .key("copied.txt")
+        .send()
+        .await?;
+    assert_eq!(copied.content_type(), Some("text/plain"));
+    let metadata = copied.metadata().unwrap();
+    assert_eq!(metadata.get("author").map(String::as_str), Some("me"));
+    assert!(!metadata.contains_key("reviewed"));
+
+    let replaced = c
+        .head_object()
+        .bucket(&bucket_with_owner)
+        .key("replaced.txt")
+        .send()
+        .await?;
+    assert_eq!(replaced.content_type(), Some("application/json"));
+    let metadata = replaced.metadata().unwrap();
+    assert_eq!(metadata.get("reviewed").map(String::as_str), Some("yes"));
+    assert!(!metadata.contains_key("author"));
+
+    // Either way the bytes, and so the etag, are the source's.
+    assert_eq!(copied.e_tag(), replaced.e_tag());
+
+    for key in [key, "copied.txt", "replaced.txt"] {
+        delete_object(&c, &bucket_with_owner, key).await?;
+    }
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_copy_object_across_buckets() -> Result<()> {
+    let _guard = serial().await;
+    let config = config().await;
+    let c = Client::new(&config.sdk);
+
+    let src_bucket = "test-copy-src";
+    let dst_bucket = "test-copy-dst";
+    let src_with_owner = format!("{}.{}", &config.address, src_bucket);
+    let dst_with_owner = format!("{}.{}", &config.address, dst_bucket);
+    create_bucket(&c, src_bucket).await?;
+    create_bucket(&c, dst_bucket).await?;
+
+    let key = "moved/data.txt";
+    c.put_object()
+        .bucket(&src_with_owner)
+        .key(key)
+        .metadata("author", "me")
+        .body(ByteStream::from_static(b"hello world\n"))
+        .send()
+        .await?;
+
+    // wait for object resolution
+    sleep(Duration::from_millis(5000)).await;
+
+    c.copy_object()
+        .bucket(&dst_with_owner)
+        .key(key)
+        .copy_source(format!("{}/{}", &src_with_owner, key))
+        .send()
+        .await?;
+
+    // wait for object resolution
+    sleep(Duration::from_millis(5000)).await;
+
+    let list = c
+        .list_objects_v2()
+        .bucket(&dst_with_owner)
+        .prefix("moved/")
+        .send()
+        .await?;
+    let keys: Vec<_> = list.contents().iter().filter_map(|o| o.key()).collect();
+    assert_eq!(keys, [key]);
+
+    let head = c
+        .head_object()
+        .bucket(&dst_with_owner)
+        .key(key)
+        .send()
+        .await?;
+    assert_eq!(
+        head.metadata().unwrap().get("author").map(String::as_str),
+        Some("me")
+    );
+
+    // The source is left where it was.
+    c.head_object()
+        .bucket(&src_with_owner)
+        .key(key)
+        .send()
+        .await?;
+
+    delete_object(&c, &src_with_owner, key).await?;
+    delete_object(&c, &dst_with_owner, key).await?;
+    Ok(())
+}
+
+/// A gateway of its own on the local network, for tests that need it configured differently
+/// from the shared one. Requests refused before the backend is asked don't need the network up.
+fn test_recall(
+    wallet: Option<Wallet>,
+) -> Result<Recall<impl recall_provider::Client + Send + Sync, Wallet>> {
+    let network = Network::Localnet;
+    let provider = JsonRpcProvider::new_http(
+        network.get_config().rpc_url,
+        network.get_config().subnet_id.chain_id(),
+        None,
+        Some(network.get_config().object_api_url),
+    )?;
+    Ok(Recall::new(tempdir()?.into_path(), provider, wallet)?)
+}
+
+/// A wallet that owns none of the suite's buckets.
+fn foreign_wallet() -> Result<Wallet> {
+    let sk = parse_secret_key("1c323d494d1d069fe4c891350a1ec691c4216c17418a0cb3c7533b143bd2b812")?;
+    Ok(Wallet::new_secp256k1(
+        sk,
+        AccountKind::Ethereum,
+        Network::Localnet.get_config().subnet_id,
+    )?)
+}
+
+/// Client for a service of its own, for tests that need the gateway configured differently from
+/// the shared one.
+async fn standalone_client(s3: impl s3s::S3) -> Client {
+    let sdk = config()
+        .await
+        .sdk
+        .to_builder()
+        .http_client(s3s_aws::Client::from(
+            S3ServiceBuilder::new(s3).build().into_shared(),
+        ))
+        .build();
+    Client::new(&sdk)
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_disable_multipart() -> Result<()> {
+    // It's refused before the bucket is looked up, so the gateway doesn't need a wallet.
+    let mut recall = test_recall(None)?;
+    recall.disable_multipart = true;
+    let c = standalone_client(recall).await;
+
+    let err = c
+        .create_multipart_upload()
+        .bucket("0xc05fe6b63ffa4b3c518e6ff1e597358ee839db01.test-disable-multipart")
+        .key("large.bin")
+        .send()
+        .await
+        .unwrap_err();
+    let err = err.into_service_error();
+    assert_eq!(err.code(), Some("NotImplemented"));
+    assert!(err.message().unwrap().contains("PutObject"));
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_read_only_rejects_writes() -> Result<()> {
+    let c = standalone_client(test_recall(None)?).await;
+
+    let bucket = "0xc05fe6b63ffa4b3c518e6ff1e597358ee839db01.test-read-only";
+    let put_err = c
+        .put_object()
+        .bucket(bucket)
+        .key("a.txt")
+        .body(ByteStream::from_static(b"a"))
+        .send()
+        .await
+        .unwrap_err();
+    let delete_err = c
+        .delete_object()
+        .bucket(bucket)
+        .key("a.txt")
+        .send()
+        .await
+        .unwrap_err();
+
+    // Denied, not unsupported: clients shouldn't retry or look for another way to write.
+    for (status, code) in [
+        (
+            put_err.raw_response().unwrap().status().as_u16(),
+            put_err.into_service_error().code().map(str::to_owned),
+        ),
+        (
+            delete_err.raw_response().unwrap().status().as_u16(),
+            delete_err.into_service_error().code().map(str::to_owned),
+        ),
+    ] {
+        assert_eq!(status, 403);
+        assert_eq!(code.as_deref(), Some("AccessDenied"));
+    }
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_tenant_quota() -> Result<()> {
+    let _guard = serial().await;
+    let config = config().await;
+    let c = Client::new(&config.sdk);
+    let (access_key, secret_key, _) = TENANTS[2];
+    let tenant = tenant_client(config, access_key, secret_key);
+
+    let bucket = "test-quota";
+    let bucket_with_owner = format!("{}.{}", &config.address, bucket);
+    create_bucket(&c, bucket).await?;
+
+    let content = vec![b'q'; usize::try_from(TENANT_QUOTA * 6 / 10)?];
+    let put = |key: &'static str| {
+        tenant
+            .put_object()
+            .bucket(&bucket_with_owner)
+            .key(key)
+            .body(ByteStream::from(content.clone()))
+            .send()
+    };
+
+    put("first.bin").await?;
+
+    // wait for object resolution
+    sleep(Duration::from_millis(5000)).await;
+
+    let err = put("second.bin").await.unwrap_err();
+    assert_eq!(err.raw_response().unwrap().status().as_u16(), 403);
+    assert_eq!(err.into_service_error().code(), Some("QuotaExceeded"));
+
+    // Overwriting only counts the difference, and deleting frees the space again.
+    put("first.bin").await?;
+    sleep(Duration::from_millis(5000)).await;
+    tenant
+        .delete_object()
+        .bucket(&bucket_with_owner)
+        .key("first.bin")
+        .send()
+        .await?;
+    put("second.bin").await?;
+    sleep(Duration::from_millis(5000)).await;
+
+    // The admin credentials aren't limited.
+    c.put_object()
+        .bucket(&bucket_with_owner)
+        .key("admin.bin")
+        .body(ByteStream::from(vec![
+            b'a';
+            usize::try_from(TENANT_QUOTA * 2)?
+        ]))
+        .send()
+        .await?;
+
+    // The space goes back to the tenant that stored the object, whoever deletes it.
+    delete_object(&c, &bucket_with_owner, "tenants/quota/second.bin").await?;
+    put("third.bin").await?;
+    sleep(Duration::from_millis(5000)).await;
+
+    tenant
+        .delete_object()
+        .bucket(&bucket_with_owner)
+        .key("third.bin")
+        .send()
+        .await?;
+    delete_object(&c, &bucket_with_owner, "admin.bin").await?;
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_list_objects_v2_encoded_keys() -> Result<()> {
+    let _guard = serial().await;
+
+    let config = config().await;
+    let c = Client::new(&config.sdk);
+
+    let bucket = "test-list-encoded";
+    let bucket_with_owner = format!("{}.{}", &config.address, bucket);
+
+    create_bucket(&c, bucket).await?;
+
+    let keys = ["encoded/a b+c%d.txt", "encoded/ÿ&<>.txt"];
+    for key in keys {
+        c.put_object()
+            .bucket(&bucket_with_owner)
+            .key(key)
+            .body(ByteStream::from_static(b"hello world\n"))
+            .send()
+            .await?;
+    }
+
+    // wait for object resolution
+    sleep(Duration::from_millis(5000)).await;
+
+    let ans = c
+        .list_objects_v2()
+        .bucket(&bucket_with_owner)
+        .prefix("encoded/")
+        .encoding_type(aws_sdk_s3::types::EncodingType::Url)
+        .send()
+        .await?;
+    // the SDK hands back the keys still encoded
+    let decode = |v: &str| -> String {
+        url::form_urlencoded::parse(v.as_bytes())
+            .map(|(k, _)| k.into_owned())
+            .next()
+            .unwrap_or_default()
+    };
+    let contents: Vec<_> = ans
+        .contents()
+        .iter()
+        .filter_map(|obj| obj.key())
+        .map(decode)
+        .collect();
+    assert_eq!(contents, keys);
+    assert_eq!(ans.prefix().map(decode).as_deref(), Some("encoded/"));
+
+    for key in keys {
+        delete_object(&c, &bucket_with_owner, key).await?;
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_object_lock() -> Result<()> {
+    let _guard = serial().await;
+    let config = config().await;
+    let c = Client::new(&config.sdk);
+
+    let bucket = "test-object-lock";
+    let bucket_with_owner = format!("{}.{}", &config.address, bucket);
+    let key = "record.txt";
+    create_bucket(&c, bucket).await?;
+
+    c.put_object()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .body(ByteStream::from_static(b"keep me"))
+        .send()
+        .await?;
+
+    // wait for object resolution
+    sleep(Duration::from_millis(5000)).await;
+
+    let err = c
+        .get_object_retention()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .send()
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.into_service_error().code(),
+        Some("NoSuchObjectLockConfiguration")
+    );
+
+    let retain_until = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() + 3600;
+    c.put_object_retention()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .retention(
+            ObjectLockRetention::builder()
+                .mode(ObjectLockRetentionMode::Governance)
+                .retain_until_date(DateTime::from_secs(i64::try_from(retain_until)?))
+                .build(),
+        )
+        .send()
+        .await?;
+    c.put_object_legal_hold()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .legal_hold(
+            ObjectLockLegalHold::builder()
+                .status(ObjectLockLegalHoldStatus::On)
+                .build(),
+        )
+        .send()
+        .await?;
+
+    // wait for object resolution
+    sleep(Duration::from_millis(5000)).await;
+
+    let retention = c
+        .get_object_retention()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .send()
+        .await?;
+    let retention = retention.retention().unwrap();
+    assert_eq!(retention.mode(), Some(&ObjectLockRetentionMode::Governance));
+    assert_eq!(
+        retention.retain_until_date().map(DateTime::secs),
+        Some(i64::try_from(retain_until)?)
+    );
+    let legal_hold = c
+        .get_object_legal_hold()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .send()
+        .await?;
+    assert_eq!(
+        legal_hold.legal_hold().unwrap().status(),
+        Some(&ObjectLockLegalHoldStatus::On)
+    );
+    let head = c
+        .head_object()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .send()
+        .await?;
+    assert_eq!(head.object_lock_mode(), Some(&ObjectLockMode::Governance));
+
+    // Governance retention can be bypassed, but a legal hold can't.
+    let err = c
+        .delete_object()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .bypass_governance_retention(true)
+        .send()
+        .await
+        .unwrap_err();
+    assert_eq!(err.into_service_error().code(), Some("AccessDenied"));
+
+    c.put_object_legal_hold()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .legal_hold(
+            ObjectLockLegalHold::builder()
+                .status(ObjectLockLegalHoldStatus::Off)
+                .build(),
+        )
+        .send()
+        .await?;
+
+    // wait for object resolution
+    sleep(Duration::from_millis(5000)).await;
+
+    let err = c
+        .delete_object()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .send()
+        .await
+        .unwrap_err();
+    assert_eq!(err.into_service_error().code(), Some("AccessDenied"));
+
+    c.delete_object()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .bypass_governance_retention(true)
+        .send()
+        .await?;
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_object_lock_overwrite() -> Result<()> {
+    let _guard = serial().await;
+    let config = config().await;
+    let c = Client::new(&config.sdk);
+
+    let bucket = "test-object-lock-overwrite";
+    let bucket_with_owner = format!("{}.{}", &config.address, bucket);
+    let key = "held.txt";
+    create_bucket(&c, bucket).await?;
+
+    c.put_object()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .body(ByteStream::from_static(b"keep me"))
+        .send()
+        .await?;
+    c.put_object()
+        .bucket(&bucket_with_owner)
+        .key("other.txt")
+        .body(ByteStream::from_static(b"replace me"))
+        .send()
+        .await?;
+
+    // wait for object resolution
+    sleep(Duration::from_millis(5000)).await;
+
+    c.put_object_legal_hold()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .legal_hold(
+            ObjectLockLegalHold::builder()
+                .status(ObjectLockLegalHoldStatus::On)
+                .build(),
+        )
+        .send()
+        .await?;
+
+    // wait for object resolution
+    sleep(Duration::from_millis(5000)).await;
+
+    let err = c
+        .put_object()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .body(ByteStream::from_static(b"overwritten"))
+        .send()
+        .await
+        .unwrap_err();
+    assert_eq!(err.into_service_error().code(), Some("AccessDenied"));
+
+    let err = c
+        .copy_object()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .copy_source(format!("{bucket_with_owner}/other.txt"))
+        .send()
+        .await
+        .unwrap_err();
+    assert_eq!(err.into_service_error().code(), Some("AccessDenied"));
+
+    // A forced bucket delete is refused as a whole, so the unlocked object survives too.
+    let err = c
+        .delete_bucket()
+        .bucket(&bucket_with_owner)
+        .customize()
+        .mutate_request(|req| {
+            req.headers_mut().insert("x-recall-force", "true");
+        })
+        .send()
+        .await
+        .unwrap_err();
+    assert_eq!(err.into_service_error().code(), Some("AccessDenied"));
+
+    let held = c
+        .get_object()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .send()
+        .await?;
+    assert_eq!(held.body.collect().await?.into_bytes().as_ref(), b"keep me");
+    c.head_object()
+        .bucket(&bucket_with_owner)
+        .key("other.txt")
+        .send()
+        .await?;
+
+    c.put_object_legal_hold()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .legal_hold(
+            ObjectLockLegalHold::builder()
+                .status(ObjectLockLegalHoldStatus::Off)
+                .build(),
+        )
+        .send()
+        .await?;
+
+    // wait for object resolution
+    sleep(Duration::from_millis(5000)).await;
+
+    delete_object(&c, &bucket_with_owner, key).await?;
+    delete_object(&c, &bucket_with_owner, "other.txt").await?;
+    Ok(())
+}
+
+/// Uploads `parts` as a multipart upload of `key` and returns the etag of the completed object.
+async fn multipart_upload(c: &Client, bucket: &str, key: &str, parts: &[&[u8]]) -> Result<String> {
+    let upload_id = c
+        .create_multipart_upload()
+        .bucket(bucket)
+        .key(key)
+        .send()
+        .await?
+        .upload_id
+        .unwrap();
+
+    let mut completed = Vec::new();
+    for (part_number, part) in (1..).zip(parts) {
+        let ans = c
+            .upload_part()
+            .bucket(bucket)
+            .key(key)
+            .upload_id(&upload_id)
+            .body(ByteStream::from(part.to_vec()))
+            .part_number(part_number)
+            .send()
+            .await?;
+        completed.push(
+            CompletedPart::builder()
+                .e_tag(ans.e_tag.unwrap_or_default())
+                .part_number(part_number)
+                .build(),
+        );
+    }
+
+    let ans = c
+        .complete_multipart_upload()
+        .bucket(bucket)
+        .key(key)
+        .multipart_upload(
+            CompletedMultipartUpload::builder()
+                .set_parts(Some(completed))
+                .build(),
+        )
+        .upload_id(&upload_id)
+        .send()
+        .await?;
+    Ok(ans.e_tag.unwrap())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_multipart_parts_in_backend() -> Result<()> {
+    let _guard = serial().await;
+
+    let config = config().await;
+    let c = Client::new(&config.sdk);
+
+    let mut recall = test_recall(Some(get_wallet().await))?;
+    recall.multipart_parts_in_backend = true;
+    let backend = standalone_client(recall).await;
+
+    let bucket = "test-multipart-backend";
+    let bucket_with_owner = format!("{}.{}", &config.address, bucket);
+    create_bucket(&c, bucket).await?;
+
+    // The same upload through both paths gives the same object and etag.
+    let parts: [&[u8]; 3] = [&[b'a'; 1024], &[b'b'; 1024], b"tail"];
+    let file_e_tag = multipart_upload(&c, &bucket_with_owner, "file.bin", &parts).await?;
+    let backend_e_tag =
+        multipart_upload(&backend, &bucket_with_owner, "backend.bin", &parts).await?;
+    assert_eq!(backend_e_tag, file_e_tag);
+
+    // wait for object resolution
+    sleep(Duration::from_millis(5000)).await;
+
+    let expected = parts.concat();
+    for key in ["file.bin", "backend.bin"] {
+        let ans = c
+            .get_object()
+            .bucket(&bucket_with_owner)
+            .key(key)
+            .send()
+            .await?;
+        assert_eq!(ans.e_tag.unwrap(), file_e_tag);
+        assert_eq!(ans.body.collect().await?.into_bytes().as_ref(), expected);
+    }
+
+    // Parts of an upload in progress don't show up as objects, and aborting removes them.
+    let upload_id = backend
+        .create_multipart_upload()
+        .bucket(&bucket_with_owner)
+        .key("aborted.bin")
+        .send()
+        .await?
+        .upload_id
+        .unwrap();
+    backend
+        .upload_part()
+        .bucket(&bucket_with_owner)
+        .key("aborted.bin")
+        .upload_id(&upload_id)
+        .body(ByteStream::from_static(b"part"))
+        .part_number(1)
+        .send()
+        .await?;
+    sleep(Duration::from_millis(5000)).await;
+
+    let listed = c
+        .list_objects_v2()
+        .bucket(&bucket_with_owner)
+        .send()
+        .await?;
+    let mut keys: Vec<_> = listed.contents().iter().filter_map(|o| o.key()).collect();
+    keys.sort_unstable();
+    assert_eq!(keys, ["backend.bin", "file.bin"]);
+
+    backend
+        .abort_multipart_upload()
+        .bucket(&bucket_with_owner)
+        .key("aborted.bin")
+        .upload_id(&upload_id)
+        .send()
+        .await?;
+
+    delete_object(&c, &bucket_with_owner, "file.bin").await?;
+    delete_object(&c, &bucket_with_owner, "backend.bin").await?;
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_get_object_foreign_owner() -> Result<()> {
+    let _guard = serial().await;
+
+    let config = config().await;
+    let c = Client::new(&config.sdk);
+
+    let bucket = "test-foreign-owner";
+    let bucket_with_owner = format!("{}.{}", &config.address, bucket);
+    create_bucket(&c, bucket).await?;
+    c.put_object()
+        .bucket(&bucket_with_owner)
+        .key("public.txt")
+        .body(ByteStream::from_static(b"public"))
+        .send()
+        .await?;
+
+    // wait for object resolution
+    sleep(Duration::from_millis(5000)).await;
+
+    // A gateway with a wallet of its own, reading buckets it doesn't own.
+    let gateway = standalone_client(test_recall(Some(foreign_wallet()?))?).await;
+
+    // buckets are public, so another owner's bucket can be read by naming the owner
+    let ans = gateway
+        .get_object()
+        .bucket(&bucket_with_owner)
+        .key("public.txt")
+        .send()
+        .await?;
+    assert_eq!(ans.body.collect().await?.into_bytes().as_ref(), b"public");
+
+    // a valid owner without such a bucket, whether or not they have others
+    for missing in [
+        format!("{}.test-no-such-bucket", &config.address),
+        format!("0xe1209fb9aa2d08c8541297ec06ee6bbb63b10edc.{bucket}"),
+    ] {
+        let err = gateway
+            .get_object()
+            .bucket(&missing)
+            .key("public.txt")
+            .send()
+            .await
+            .unwrap_err();
+        assert_eq!(err.raw_response().unwrap().status().as_u16(), 404);
+        assert_eq!(err.into_service_error().code(), Some("NoSuchBucket"));
+    }
+
+    // a mistyped owner isn't read as a bucket of the gateway's wallet
+    let malformed = format!("{}.{bucket}", &config.address[..41]);
+    let err = gateway
+        .get_object()
+        .bucket(&malformed)
+        .key("public.txt")
+        .send()
+        .await
+        .unwrap_err();
+    assert_eq!(err.raw_response().unwrap().status().as_u16(), 400);
+    assert_eq!(err.into_service_error().code(), Some("InvalidBucketName"));
+
+    delete_object(&c, &bucket_with_owner, "public.txt").await?;
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_owner_header() -> Result<()> {
+    let _guard = serial().await;
+
+    let config = config().await;
+    let c = Client::new(&config.sdk);
+
+    let bucket = "test-owner-header";
+    let bucket_with_owner = format!("{}.{}", &config.address, bucket);
+    create_bucket(&c, bucket).await?;
+    c.put_object()
+        .bucket(&bucket_with_owner)
+        .key("shared.txt")
+        .body(ByteStream::from_static(b"shared"))
+        .send()
+        .await?;
+
+    // wait for object resolution
+    sleep(Duration::from_millis(5000)).await;
+
+    // A gateway paying with a wallet of its own, which doesn't own the bucket.
+    let recall = test_recall(Some(foreign_wallet()?))?;
+    let cred = Credentials::for_tests();
+    let service = {
+        let mut b = S3ServiceBuilder::new(recall);
+        b.set_auth(SimpleAuth::from_single(
+            cred.access_key_id(),
+            cred.secret_access_key(),
+        ));
+        b.set_base_domain(DOMAIN_NAME);
+        b.build()
+    };
+    let sdk = config
+        .sdk
+        .to_builder()
+        .http_client(s3s_aws::Client::from(service.into_shared()))
+        .build();
+    let gateway = Client::new(&sdk);
+
+    // Without the header the bucket is looked up under the gateway's wallet.
+    let err = gateway
+        .get_object()
+        .bucket(bucket)
+        .key("shared.txt")
+        .send()
+        .await
+        .unwrap_err();
+    assert_eq!(err.into_service_error().code(), Some("NoSuchBucket"));
+
+    let owner = config.address.clone();
+    let ans = gateway
+        .get_object()
+        .bucket(bucket)
+        .key("shared.txt")
+        .customize()
+        .mutate_request(move |req| {
+            req.headers_mut().insert("x-recall-owner", owner.clone());
+        })
+        .send()
+        .await?;
+    assert_eq!(ans.body.collect().await?.into_bytes().as_ref(), b"shared");
+
+    let err = gateway
+        .list_objects_v2()
+        .bucket(bucket)
+        .customize()
+        .mutate_request(|req| {
+            req.headers_mut().insert("x-recall-owner", "not-an-address");
+        })
+        .send()
+        .await
+        .unwrap_err();
+    assert_eq!(err.into_service_error().code(), Some("InvalidArgument"));
+
+    delete_object(&c, &bucket_with_owner, "shared.txt").await?;
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_list_objects_v1_marker() -> Result<()> {
+    let _guard = serial().await;
+
+    let config = config().await;
+    let c = Client::new(&config.sdk);
+
+    let bucket = "test-list-v1";
+    let bucket_with_owner = format!("{}.{}", &config.address, bucket);
+    create_bucket(&c, bucket).await?;
+
+    let keys = ["a.txt", "b/1.txt", "b/2.txt", "c.txt"];
+    for key in keys {
+        c.put_object()
+            .bucket(&bucket_with_owner)
+            .key(key)
+            .body(ByteStream::from_static(b"v1"))
+            .send()
+            .await?;
+    }
+
+    // wait for object resolution
+    sleep(Duration::from_millis(5000)).await;
+
+    // Page through with the marker, as v1 clients do.
+    let mut listed = Vec::new();
+    let mut marker = None;
+    loop {
+        let ans = c
+            .list_objects()
+            .bucket(&bucket_with_owner)
+            .max_keys(2)
+            .set_marker(marker.clone())
+            .send()
+            .await?;
+        assert_eq!(ans.max_keys(), Some(2));
+        assert_eq!(ans.marker(), marker.as_deref());
+        listed.extend(
+            ans.contents()
+                .iter()
+                .filter_map(|o| o.key().map(str::to_owned)),
+        );
+        if !ans.is_truncated().unwrap_or_default() {
+            assert!(ans.next_marker().is_none());
+            break;
+        }
+        marker = ans.next_marker().map(str::to_owned);
+        assert!(marker.is_some());
+    }
+    assert_eq!(listed, keys);
+
+    // A marker naming a common prefix skips every key rolled up into it.
+    let ans = c
+        .list_objects()
+        .bucket(&bucket_with_owner)
+        .delimiter("/")
+        .marker("b/")
+        .send()
+        .await?;
+    let keys_after: Vec<_> = ans.contents().iter().filter_map(|o| o.key()).collect();
+    assert_eq!(keys_after, ["c.txt"]);
+    assert!(ans.common_prefixes().is_empty());
+
+    for key in keys {
+        delete_object(&c, &bucket_with_owner, key).await?;
+    }
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_storage_class() -> Result<()> {
+    let _guard = serial().await;
+
+    let config = config().await;
+    let c = Client::new(&config.sdk);
+
+    let bucket = "test-storage-class";
+    let bucket_with_owner = format!("{}.{}", &config.address, bucket);
+    create_bucket(&c, bucket).await?;
+
+    c.put_object()
+        .bucket(&bucket_with_owner)
+        .key("standard.txt")
+        .storage_class(StorageClass::Standard)
+        .body(ByteStream::from_static(b"standard"))
+        .send()
+        .await?;
+
+    // The backend has a single durability tier to offer.
+    for class in [StorageClass::ReducedRedundancy, StorageClass::Glacier] {
+        let err = c
+            .put_object()
+            .bucket(&bucket_with_owner)
+            .key("rejected.txt")
+            .storage_class(class)
+            .body(ByteStream::from_static(b"rejected"))
+            .send()
+            .await
+            .unwrap_err();
+        assert_eq!(err.into_service_error().code(), Some("InvalidStorageClass"));
+    }
+
+    // wait for object resolution
+    sleep(Duration::from_millis(5000)).await;
+
+    let head = c
+        .head_object()
+        .bucket(&bucket_with_owner)
+        .key("standard.txt")
+        .send()
+        .await?;
+    assert_eq!(head.storage_class(), Some(&StorageClass::Standard));
+
+    let get = c
+        .get_object()
+        .bucket(&bucket_with_owner)
+        .key("standard.txt")
+        .send()
+        .await?;
+    assert_eq!(get.storage_class(), Some(&StorageClass::Standard));
+
+    let list = c
+        .list_objects_v2()
+        .bucket(&bucket_with_owner)
+        .send()
+        .await?;
+    assert!(list
+        .contents()
+        .iter()
+        .all(|o| o.storage_class() == Some(&ObjectStorageClass::Standard)));
+
+    delete_object(&c, &bucket_with_owner, "standard.txt").await?;
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_percent_encoded_key() -> Result<()> {
+    let _guard = serial().await;
+
+    let config = config().await;
+    let c = Client::new(&config.sdk);
+
+    let bucket = "test-percent-encoded-key";
+    let bucket_with_owner = format!("{}.{}", &config.address, bucket);
+    create_bucket(&c, bucket).await?;
+
+    // The SDK sends this as `a%20b%2Bc%2520d.txt`. Decoding it twice would store `a b+c d.txt`.
+    let key = "percent/a b+c%20d.txt";
+    c.put_object()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .body(ByteStream::from_static(b"hello world\n"))
+        .send()
+        .await?;
+
+    // wait for object resolution
+    sleep(Duration::from_millis(5000)).await;
+
+    let get = c
+        .get_object()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .send()
+        .await?;
+    assert_eq!(
+        get.body.collect().await?.into_bytes().as_ref(),
+        b"hello world\n"
+    );
+
+    let list = c
+        .list_objects_v2()
+        .bucket(&bucket_with_owner)
+        .prefix("percent/")
+        .send()
+        .await?;
+    let keys: Vec<_> = list.contents().iter().filter_map(|o| o.key()).collect();
+    assert_eq!(keys, [key]);
+
+    delete_object(&c, &bucket_with_owner, key).await?;
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_head_object_range() -> Result<()> {
+    let _guard = serial().await;
+
+    let config = config().await;
+    let c = Client::new(&config.sdk);
+
+    let bucket = "test-head-object-range";
+    let bucket_with_owner = format!("{}.{}", &config.address, bucket);
+    create_bucket(&c, bucket).await?;
+
+    let key = "range.txt";
+    c.put_object()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .body(ByteStream::from_static(b"hello world, hello range\n"))
+        .send()
+        .await?;
+
+    // wait for object resolution
+    sleep(Duration::from_millis(5000)).await;
+
+    let content_range = CaptureHeader::new("content-range");
+    let head = c
+        .head_object()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .range("bytes=0-9")
+        .customize()
+        .interceptor(content_range.clone())
+        .send()
+        .await?;
+    assert_eq!(head.content_length(), Some(10));
+    assert_eq!(head.accept_ranges(), Some("bytes"));
+    assert_eq!(content_range.status(), Some(206));
+    assert_eq!(content_range.get().as_deref(), Some("bytes 0-9/25"));
+
+    // HEAD responses have no body, so only the status tells the error apart.
+    let err = c
+        .head_object()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .range("bytes=100-")
+        .send()
+        .await
+        .unwrap_err();
+    assert_eq!(err.raw_response().map(|r| r.status().as_u16()), Some(416));
+
+    delete_object(&c, &bucket_with_owner, key).await?;
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_head_object_exact_key() -> Result<()> {
+    let _guard = serial().await;
+
+    let config = config().await;
+    let c = Client::new(&config.sdk);
+
+    let bucket = "test-head-object-exact-key";
+    let bucket_with_owner = format!("{}.{}", &config.address, bucket);
+    create_bucket(&c, bucket).await?;
+
+    c.put_object()
+        .bucket(&bucket_with_owner)
+        .key("ab")
+        .body(ByteStream::from_static(b"ab"))
+        .send()
+        .await?;
+
+    // wait for object resolution
+    sleep(Duration::from_millis(5000)).await;
+
+    // "a" is a prefix of "ab", but not its key
+    let err = c
+        .head_object()
+        .bucket(&bucket_with_owner)
+        .key("a")
+        .send()
+        .await
+        .unwrap_err();
+    assert_eq!(err.raw_response().map(|r| r.status().as_u16()), Some(404));
+
+    let head = c
+        .head_object()
+        .bucket(&bucket_with_owner)
+        .key("ab")
+        .send()
+        .await?;
+    assert_eq!(head.content_length(), Some(2));
+
+    delete_object(&c, &bucket_with_owner, "ab").await?;
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_missing_owner_is_client_error() -> Result<()> {
+    let c = standalone_client(test_recall(None)?).await;
+
+    // Without a wallet there's no default owner, so the name alone can't be resolved. That's
+    // the client's mistake, not a server failure worth retrying.
+    let err = c
+        .get_object()
+        .bucket("test-missing-owner")
+        .key("a.txt")
+        .send()
+        .await
+        .unwrap_err();
+    assert_eq!(err.raw_response().unwrap().status().as_u16(), 400);
+    assert_eq!(err.into_service_error().code(), Some("InvalidBucketName"));
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_invalid_object_key() -> Result<()> {
+    let config = config().await;
+    let c = Client::new(&config.sdk);
+
+    // Keys are checked before the bucket is looked up, so it needn't exist.
+    let bucket = format!("{}.test-invalid-key", &config.address);
+    for key in ["a\0b".to_string(), "a".repeat(1025)] {
+        let err = c
+            .put_object()
+            .bucket(&bucket)
+            .key(&key)
+            .body(ByteStream::from_static(b"hello"))
+            .send()
+            .await
+            .unwrap_err();
+        let code = err.into_service_error().code().map(ToString::to_string);
+        // s3s already refuses over-long keys in the path
+        assert!(
+            matches!(code.as_deref(), Some("InvalidArgument" | "KeyTooLongError")),
+            "{code:?}"
+        );
+    }
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_reserved_object_key() -> Result<()> {
+    let config = config().await;
+    let c = Client::new(&config.sdk);
+
+    // Multipart parts are kept under this prefix and hidden from listings, so no write may use it.
+    let bucket = format!("{}.test-reserved-key", &config.address);
+    let key = ".recall-s3-uploads/upload/1";
+
+    let err = c
+        .put_object()
+        .bucket(&bucket)
+        .key(key)
+        .body(ByteStream::from_static(b"hello"))
+        .send()
+        .await
+        .unwrap_err();
+    assert_eq!(err.into_service_error().code(), Some("InvalidArgument"));
+
+    let err = c
+        .copy_object()
+        .bucket(&bucket)
+        .key(key)
+        .copy_source(format!("{bucket}/a.txt"))
+        .send()
+        .await
+        .unwrap_err();
+    assert_eq!(err.into_service_error().code(), Some("InvalidArgument"));
+
+    let err = c
+        .create_multipart_upload()
+        .bucket(&bucket)
+        .key(key)
+        .send()
+        .await
+        .unwrap_err();
+    assert_eq!(err.into_service_error().code(), Some("InvalidArgument"));
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_write_to_foreign_bucket() -> Result<()> {
+    let config = config().await;
+    let c = Client::new(&config.sdk);
+
+    // Writes are signed by the gateway's wallet, so another address's bucket is refused before
+    // anything reaches the chain.
+    let bucket = "0xe1209fb9aa2d08c8541297ec06ee6bbb63b10edc.foreign";
+    let err = c
+        .put_object()
+        .bucket(bucket)
+        .key("a.txt")
+        .body(ByteStream::from_static(b"hello"))
+        .send()
+        .await
+        .unwrap_err();
+    assert_eq!(err.raw_response().unwrap().status().as_u16(), 403);
+    assert_eq!(err.into_service_error().code(), Some("AccessDenied"));
+
+    let err = c
+        .delete_object()
+        .bucket(bucket)
+        .key("a.txt")
+        .send()
+        .await
+        .unwrap_err();
+    assert_eq!(err.into_service_error().code(), Some("AccessDenied"));
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_complete_multipart_upload_if_match() -> Result<()> {
+    let _guard = serial().await;
+
+    let config = config().await;
+    let c = Client::new(&config.sdk);
+
+    let bucket = "test-complete-if-match";
+    let bucket_with_owner = format!("{}.{}", &config.address, bucket);
+    let key = "changing.txt";
+    create_bucket(&c, bucket).await?;
+
+    let put = |body: &'static [u8]| {
+        c.put_object()
+            .bucket(&bucket_with_owner)
+            .key(key)
+            .body(ByteStream::from_static(body))
+            .send()
+    };
+    let stale_e_tag = put(b"first").await?.e_tag.unwrap();
+    // wait for object resolution
+    sleep(Duration::from_millis(5000)).await;
+    // the object changes while the upload runs
+    let current_e_tag = put(b"second").await?.e_tag.unwrap();
+    sleep(Duration::from_millis(5000)).await;
+
+    let upload_id = c
+        .create_multipart_upload()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .send()
+        .await?
+        .upload_id
+        .unwrap();
+    let part = c
+        .upload_part()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .upload_id(&upload_id)
+        .body(ByteStream::from_static(b"third"))
+        .part_number(1)
+        .send()
+        .await?;
+    let complete = |if_match: String| {
+        c.complete_multipart_upload()
+            .bucket(&bucket_with_owner)
+            .key(key)
+            .upload_id(&upload_id)
+            .multipart_upload(
+                CompletedMultipartUpload::builder()
+                    .parts(
+                        CompletedPart::builder()
+                            .e_tag(part.e_tag().unwrap_or_default())
+                            .part_number(1)
+                            .build(),
+                    )
+                    .build(),
+            )
+            .customize()
+            .mutate_request(move |req| {
+                req.headers_mut().insert("if-match", if_match.clone());
+            })
+            .send()
+    };
+
+    let err = complete(stale_e_tag).await.unwrap_err();
+    assert_eq!(err.into_service_error().code(), Some("PreconditionFailed"));
+
+    // the upload is still there to complete against the object as it is now
+    complete(current_e_tag).await?;
+    sleep(Duration::from_millis(5000)).await;
+    let body = c
+        .get_object()
+        .bucket(&bucket_with_owner)
+        .key(key)
+        .send()
+        .await?
+        .body
+        .collect()
+        .await?
+        .into_bytes();
+    assert_eq!(body.as_ref(), b"third");
+
+    delete_object(&c, &bucket_with_owner, key).await?;
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_head_bucket_stats() -> Result<()> {
+    let _guard = serial().await;
+
+    let config = config().await;
+    let c = Client::new(&config.sdk);
+
+    let bucket = "test-bucket-stats";
+    let bucket_with_owner = format!("{}.{}", &config.address, bucket);
+    create_bucket(&c, bucket).await?;
+
+    let objects: [(&str, &[u8]); 3] = [("a.txt", b"a"), ("b.txt", b"bb"), ("c/d.txt", b"ddd")];
+    for (key, body) in objects {
+        c.put_object()
+            .bucket(&bucket_with_owner)
+            .key(key)
+            .body(ByteStream::from_static(body))
+            .send()
+            .await?;
+    }
+
+    // wait for object resolution
+    sleep(Duration::from_millis(5000)).await;
+
+    // Without the header, nothing is counted.
+    let count = CaptureHeader::new("x-recall-object-count");
+    c.head_bucket()
+        .bucket(&bucket_with_owner)
+        .customize()
+        .interceptor(count.clone())
+        .send()
+        .await?;
+    assert_eq!(count.get(), None);
+
+    let count = CaptureHeader::new("x-recall-object-count");
+    let size = CaptureHeader::new("x-recall-total-size");
+    c.head_bucket()
+        .bucket(&bucket_with_owner)
+        .customize()
+        .mutate_request(|req| {
+            req.headers_mut().insert("x-recall-stats", "true");
+        })
+        .interceptor(count.clone())
+        .interceptor(size.clone())
+        .send()
+        .await?;
+    assert_eq!(count.get().as_deref(), Some("3"));
+    assert_eq!(size.get().as_deref(), Some("6"));
+
+    for (key, _) in objects {
+        delete_object(&c, &bucket_with_owner, key).await?;
+    }
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_null_version_id() -> Result<()> {
+    let _guard = serial().await;
+
+    let config = config().await;
+    let c = Client::new(&config.sdk);
+
+    let bucket = "test-version-id";
+    let bucket_with_owner = format!("{}.{}", &config.address, bucket);
+    create_bucket(&c, bucket).await?;
+
+    // Buckets are unversioned, so every object reports S3's stable "null" version.
+    let put = c
+        .put_object()
+        .bucket(&bucket_with_owner)
+        .key("a.txt")
+        .body(ByteStream::from_static(b"hello"))
+        .send()
+        .await?;
+    assert_eq!(put.version_id(), Some("null"));
+
+    // wait for object resolution
+    sleep(Duration::from_millis(5000)).await;
+
+    let get = c
+        .get_object()
+        .bucket(&bucket_with_owner)
+        .key("a.txt")
+        .send()
+        .await?;
+    assert_eq!(get.version_id(), Some("null"));
+
+    // and clients sending it back get the object
+    let head = c
+        .head_object()
+        .bucket(&bucket_with_owner)
+        .key("a.txt")
+        .version_id("null")
+        .send()
+        .await?;
+    assert_eq!(head.version_id(), Some("null"));
+
+    let deleted = c
+        .delete_object()
+        .bucket(&bucket_with_owner)
+        .key("a.txt")
+        .send()
+        .await?;
+    assert_eq!(deleted.version_id(), Some("null"));
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_put_object_compressed() -> Result<()> {
+    let _guard = serial().await;
+
+    let config = config().await;
+    let c = Client::new(&config.sdk);
+
+    let bucket = "test-compressed";
+    let bucket_with_owner = format!("{}.{}", &config.address, bucket);
+    create_bucket(&c, bucket).await?;
+
+    let content = "all work and no play makes jack a dull boy\n".repeat(10_000);
+    let content_len = content.len();
+    for (key, compression) in [("a.gz.txt", "gzip"), ("a.zst.txt", "zstd")] {
+        c.put_object()
+            .bucket(&bucket_with_owner)
+            .key(key)
+            .body(ByteStream::from(content.clone().into_bytes()))
+            .customize()
+            .mutate_request(move |req| {
+                req.headers_mut().insert("x-recall-compress", compression);
+            })
+            .send()
+            .await?;
+    }
+
+    // wait for object resolution
+    sleep(Duration::from_millis(5000)).await;
+
+    // Reads see the bytes as they were sent, whatever they're stored as.
+    for key in ["a.gz.txt", "a.zst.txt"] {
+        let head = c
+            .head_object()
+            .bucket(&bucket_with_owner)
+            .key(key)
+            .send()
+            .await?;
+        assert_eq!(head.content_length, Some(i64::try_from(content_len)?));
+        assert_eq!(head.content_encoding, None);
+
+        let ans = c
+            .get_object()
+            .bucket(&bucket_with_owner)
+            .key(key)
+            .send()
+            .await?;
+        assert_eq!(
+            ans.body.collect().await?.into_bytes().as_ref(),
+            content.as_bytes()
+        );
+    }
+
+    // Bucket stats count stored bytes, which are far fewer.
+    let size = CaptureHeader::new("x-recall-total-size");
+    c.head_bucket()
+        .bucket(&bucket_with_owner)
+        .customize()
+        .mutate_request(|req| {
+            req.headers_mut().insert("x-recall-stats", "true");
+        })
+        .interceptor(size.clone())
+        .send()
+        .await?;
+    let stored: usize = size.get().unwrap().parse()?;
+    assert!(stored < content_len / 10, "stored {stored} bytes");
+
+    let err = c
+        .put_object()
+        .bucket(&bucket_with_owner)
+        .key("a.br.txt")
+        .body(ByteStream::from_static(b"hello"))
+        .customize()
+        .mutate_request(|req| {
+            req.headers_mut().insert("x-recall-compress", "br");
+        })
+        .send()
+        .await
+        .unwrap_err();
+    assert_eq!(err.into_service_error().code(), Some("InvalidArgument"));
+
+    for key in ["a.gz.txt", "a.zst.txt"] {
+        delete_object(&c, &bucket_with_owner, key).await?;
+    }
+    Ok(())
+}